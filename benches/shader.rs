@@ -0,0 +1,178 @@
+// The renderer is a binary crate, so the modules the rasterizer needs are pulled in directly.
+// Their test modules are compiled without the test harness, which leaves the imports unused.
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/command.rs"]
+mod command;
+#[path = "../src/format.rs"]
+mod format;
+#[path = "../src/framebuffer.rs"]
+mod framebuffer;
+#[path = "../src/image_view.rs"]
+mod image_view;
+#[path = "../src/math.rs"]
+mod math;
+#[path = "../src/viewport.rs"]
+mod viewport;
+
+use command::{ClosureShader, Command, CullMode, FragmentBuiltins, Shader};
+use criterion::{Criterion, criterion_group, criterion_main};
+use framebuffer::Framebuffer;
+use image_view::{DepthBuffer, DepthTest, RenderTarget};
+use interpolate_macro::Interpolate;
+use math::{Color, Float2, Float3, Float4, Interpolate, Matrix4};
+use std::hint::black_box;
+use viewport::Viewport;
+
+const SIZE: u32 = 512;
+
+#[derive(Default, Clone, Copy, Interpolate)]
+struct Varyings {
+    normal: Float3,
+    uv: Float2,
+}
+
+struct Grid {
+    positions: Vec<Float3>,
+    uvs: Vec<Float2>,
+    indices: Vec<u32>,
+}
+
+struct Uniforms {
+    mvp: Matrix4,
+    light_direction: Float3,
+}
+
+// A grid of small triangles so both the per-vertex and per-pixel work show up.
+fn grid(cells: u32) -> Grid {
+    let mut positions = vec![];
+    let mut uvs = vec![];
+    for y in 0..=cells {
+        for x in 0..=cells {
+            let u = x as f32 / cells as f32;
+            let v = y as f32 / cells as f32;
+            positions.push(Float3::new(2.0 * u - 1.0, 1.0 - 2.0 * v, 0.0));
+            uvs.push(Float2::new(u, v));
+        }
+    }
+    let mut indices = vec![];
+    for y in 0..cells {
+        for x in 0..cells {
+            let i = y * (cells + 1) + x;
+            indices.extend_from_slice(&[i, i + cells + 1, i + 1, i + 1, i + cells + 1, i + cells + 2]);
+        }
+    }
+    Grid {
+        positions,
+        uvs,
+        indices,
+    }
+}
+
+fn vertex(vertex_index: u32, grid: &Grid, uniforms: &Uniforms) -> (Varyings, Float4) {
+    let position = uniforms.mvp * grid.positions[vertex_index as usize].as_point();
+    let varyings = Varyings {
+        normal: Float3::new(0.0, 0.0, 1.0),
+        uv: grid.uvs[vertex_index as usize],
+    };
+    (varyings, position)
+}
+
+fn fragment(varyings: &Varyings, _builtins: &FragmentBuiltins, uniforms: &Uniforms) -> Option<Color> {
+    let diffuse = varyings.normal.dot(-1.0 * uniforms.light_direction).max(0.0);
+    Some(Color::from(Float4::new(
+        diffuse * varyings.uv.x,
+        diffuse * varyings.uv.y,
+        diffuse,
+        1.0,
+    )))
+}
+
+struct GridShader;
+
+impl Shader for GridShader {
+    type VertexInput = Grid;
+    type Varyings = Varyings;
+    type Uniforms = Uniforms;
+    type Output = Color;
+
+    const MAY_DISCARD: bool = false;
+
+    fn vertex(&self, vertex_index: u32, grid: &Grid, uniforms: &Uniforms) -> (Varyings, Float4) {
+        vertex(vertex_index, grid, uniforms)
+    }
+
+    fn fragment(
+        &self,
+        varyings: &Varyings,
+        builtins: &FragmentBuiltins,
+        uniforms: &Uniforms,
+    ) -> Option<Color> {
+        fragment(varyings, builtins, uniforms)
+    }
+}
+
+fn render<S: Shader<VertexInput = Grid, Uniforms = Uniforms, Output = Color>>(
+    shader: &S,
+    grid: &Grid,
+    uniforms: &Uniforms,
+    render_target: &mut RenderTarget,
+    depth_buffer: &mut DepthBuffer,
+) {
+    let mut command = Command::new();
+    command.set_viewport(Viewport {
+        x_min: 0,
+        y_min: 0,
+        x_max: SIZE as i32,
+        y_max: SIZE as i32,
+    });
+    command.set_cull_mode(CullMode::None);
+    command.set_depth_test(DepthTest::Always);
+    command.set_positions(&grid.positions);
+    command.set_indices(&grid.indices);
+    let mut framebuffer = Framebuffer::new(render_target).with_depth(depth_buffer);
+    command.draw_indexed(&mut framebuffer, shader, grid, uniforms);
+}
+
+fn shader_dispatch(c: &mut Criterion) {
+    let grid = grid(64);
+    let uniforms = Uniforms {
+        mvp: Matrix4::orthographic(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0),
+        light_direction: Float3::new(0.0, 0.0, -1.0),
+    };
+    let mut render_target = RenderTarget::new(SIZE, SIZE);
+    let mut depth_buffer = DepthBuffer::new(SIZE, SIZE);
+
+    let closure_shader = ClosureShader {
+        vertex_shader: Box::new(vertex),
+        fragment_shader: Box::new(fragment),
+    };
+
+    let mut group = c.benchmark_group("shader_dispatch");
+    group.bench_function("trait", |b| {
+        b.iter(|| {
+            render(
+                black_box(&GridShader),
+                &grid,
+                &uniforms,
+                &mut render_target,
+                &mut depth_buffer,
+            )
+        })
+    });
+    group.bench_function("closure", |b| {
+        b.iter(|| {
+            render(
+                black_box(&closure_shader),
+                &grid,
+                &uniforms,
+                &mut render_target,
+                &mut depth_buffer,
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, shader_dispatch);
+criterion_main!(benches);
@@ -1,30 +1,134 @@
-// interpolate_derive/src/lib.rs
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Fields};
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, parse_quote_spanned, Data, DeriveInput, Field, Fields, Index, Member};
 
-#[proc_macro_derive(Interpolate)]
+enum Qualifier {
+    Smooth,
+    Flat,
+    NoPerspective,
+    Skip,
+}
+
+fn qualifier(field: &Field) -> syn::Result<Qualifier> {
+    let mut qualifier = Qualifier::Smooth;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("interpolate")) {
+        attr.parse_nested_meta(|meta| {
+            qualifier = if meta.path.is_ident("flat") {
+                Qualifier::Flat
+            } else if meta.path.is_ident("noperspective") {
+                Qualifier::NoPerspective
+            } else if meta.path.is_ident("skip") {
+                Qualifier::Skip
+            } else if meta.path.is_ident("smooth") {
+                Qualifier::Smooth
+            } else {
+                return Err(meta.error("expected `flat`, `noperspective`, `skip` or `smooth`"));
+            };
+            Ok(())
+        })?;
+    }
+    Ok(qualifier)
+}
+
+fn mentions_any(tokens: TokenStream2, idents: &[&syn::Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => idents.iter().any(|param| **param == ident),
+        TokenTree::Group(group) => mentions_any(group.stream(), idents),
+        _ => false,
+    })
+}
+
+#[proc_macro_derive(Interpolate, attributes(interpolate))]
 pub fn derive_interpolate(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let name = input.ident;
-
-    let fields = match input.data {
-        Data::Struct(data) => match data.fields {
-            Fields::Named(fields) => fields.named,
-            _ => panic!("Interpolate only supports named fields"),
-        },
-        _ => panic!("Interpolate only supports structs"),
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(syn::Error::new(
+                data.enum_token.span,
+                "Interpolate cannot be derived for enums",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "Interpolate cannot be derived for unions",
+            ))
+        }
     };
 
-    let interpolated_fields = fields.iter().map(|f| {
-        let name = f.ident.as_ref().unwrap();
-        quote! {
-            #name: Interpolate::interp(l0, l1, l2, &a.#name, &b.#name, &c.#name)
+    let qualifiers = fields.iter().map(qualifier).collect::<syn::Result<Vec<_>>>()?;
+
+    // Generic field types are bounded on what their qualifier needs. Concrete types are left to
+    // the generated body, whose calls are spanned to the field so errors point at its type.
+    let type_params: Vec<&syn::Ident> = input.generics.type_params().map(|param| &param.ident).collect();
+    let predicates: Vec<syn::WherePredicate> = fields
+        .iter()
+        .zip(&qualifiers)
+        .filter(|(f, _)| mentions_any(f.ty.to_token_stream(), &type_params))
+        .map(|(f, qualifier)| {
+            let ty = &f.ty;
+            let bound = match qualifier {
+                Qualifier::Smooth | Qualifier::NoPerspective => quote! { Interpolate },
+                Qualifier::Flat => quote! { ::core::clone::Clone },
+                Qualifier::Skip => quote! { ::core::default::Default },
+            };
+            parse_quote_spanned! {ty.span()=> #ty: #bound }
+        })
+        .collect();
+    input.generics.make_where_clause().predicates.extend(predicates);
+
+    let span_of = |f: &Field| f.ty.span();
+    let members: Vec<Member> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect();
+
+    // Flat fields take the value of the provoking (first) vertex, skipped fields are defaulted.
+    let interpolated_fields = fields.iter().zip(&members).zip(&qualifiers).map(|((f, member), qualifier)| {
+        match qualifier {
+            Qualifier::Flat => quote_spanned! {span_of(f)=> #member: ::core::clone::Clone::clone(&a.#member) },
+            Qualifier::Skip => quote_spanned! {span_of(f)=> #member: ::core::default::Default::default() },
+            Qualifier::Smooth | Qualifier::NoPerspective => quote_spanned! {span_of(f)=>
+                #member: Interpolate::interp(l0, l1, l2, &a.#member, &b.#member, &c.#member)
+            },
+        }
+    });
+
+    let weighted_fields = fields.iter().zip(&members).zip(&qualifiers).map(|((f, member), qualifier)| {
+        match qualifier {
+            Qualifier::Flat => quote_spanned! {span_of(f)=> #member: ::core::clone::Clone::clone(&a.#member) },
+            Qualifier::Skip => quote_spanned! {span_of(f)=> #member: ::core::default::Default::default() },
+            Qualifier::Smooth => quote_spanned! {span_of(f)=>
+                #member: Interpolate::interp_weights(perspective, screen, &a.#member, &b.#member, &c.#member)
+            },
+            Qualifier::NoPerspective => quote_spanned! {span_of(f)=>
+                #member: Interpolate::interp(screen[0], screen[1], screen[2], &a.#member, &b.#member, &c.#member)
+            },
         }
     });
 
-    let expanded = quote! {
-        impl Interpolate for #name {
+    let unused = matches!(fields, Fields::Unit).then(|| quote! { let _ = (l0, l1, l2, a, b, c); });
+    let unused_weights =
+        matches!(fields, Fields::Unit).then(|| quote! { let _ = (perspective, screen, a, b, c); });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics Interpolate for #name #ty_generics #where_clause {
             fn interp(
                 l0: f32,
                 l1: f32,
@@ -33,12 +137,24 @@ pub fn derive_interpolate(input: TokenStream) -> TokenStream {
                 b: &Self,
                 c: &Self,
             ) -> Self {
+                #unused
                 Self {
                     #(#interpolated_fields,)*
                 }
             }
-        }
-    };
 
-    expanded.into()
-}
\ No newline at end of file
+            fn interp_weights(
+                perspective: [f32; 3],
+                screen: [f32; 3],
+                a: &Self,
+                b: &Self,
+                c: &Self,
+            ) -> Self {
+                #unused_weights
+                Self {
+                    #(#weighted_fields,)*
+                }
+            }
+        }
+    })
+}
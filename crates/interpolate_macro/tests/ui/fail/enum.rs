@@ -0,0 +1,11 @@
+use interpolate_macro::Interpolate;
+
+trait Interpolate {}
+
+#[derive(Interpolate)]
+enum Varyings {
+    A(f32),
+    B,
+}
+
+fn main() {}
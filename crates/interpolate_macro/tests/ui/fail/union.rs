@@ -0,0 +1,11 @@
+use interpolate_macro::Interpolate;
+
+trait Interpolate {}
+
+#[derive(Interpolate)]
+union Varyings {
+    a: f32,
+    b: u32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use interpolate_macro::Interpolate;
+
+trait Interpolate {}
+
+#[derive(Interpolate)]
+struct Varyings {
+    #[interpolate(centroid)]
+    depth: f32,
+}
+
+fn main() {}
@@ -0,0 +1,84 @@
+#![allow(dead_code)]
+use interpolate_macro::Interpolate;
+
+trait Interpolate: Sized {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self;
+
+    fn interp_weights(perspective: [f32; 3], _screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        Self::interp(perspective[0], perspective[1], perspective[2], a, b, c)
+    }
+}
+
+impl Interpolate for f32 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        l0 * a + l1 * b + l2 * c
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        std::array::from_fn(|i| T::interp(l0, l1, l2, &a[i], &b[i], &c[i]))
+    }
+
+    fn interp_weights(perspective: [f32; 3], screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        std::array::from_fn(|i| T::interp_weights(perspective, screen, &a[i], &b[i], &c[i]))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Float2 {
+    x: f32,
+    y: f32,
+}
+
+impl Interpolate for Float2 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        Float2 {
+            x: f32::interp(l0, l1, l2, &a.x, &b.x, &c.x),
+            y: f32::interp(l0, l1, l2, &a.y, &b.y, &c.y),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Interpolate)]
+struct Varyings<T, const N: usize> {
+    value: T,
+    weights: [f32; N],
+    #[interpolate(flat)]
+    id: u32,
+}
+
+#[derive(Clone, Copy, Interpolate)]
+struct Wrapper<'a, T: Copy>
+where
+    T: Default,
+{
+    inner: T,
+    #[interpolate(skip)]
+    name: Option<&'a str>,
+}
+
+fn main() {
+    let a = Varyings {
+        value: Float2 { x: 1.0, y: 0.0 },
+        weights: [1.0, 0.0],
+        id: 1,
+    };
+    let b = Varyings {
+        value: Float2 { x: 0.0, y: 1.0 },
+        weights: [0.0, 1.0],
+        id: 2,
+    };
+    let v = Varyings::interp(0.25, 0.75, 0.0, &a, &b, &b);
+    assert_eq!(v.value.y, 0.75);
+    assert_eq!(v.weights, [0.25, 0.75]);
+    assert_eq!(v.id, 1);
+
+    let w = Wrapper {
+        inner: 2.0f32,
+        name: Some("a"),
+    };
+    let w = Wrapper::interp(1.0, 0.0, 0.0, &w, &w, &w);
+    assert_eq!(w.inner, 2.0);
+    assert!(w.name.is_none());
+}
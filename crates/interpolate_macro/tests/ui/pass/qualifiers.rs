@@ -0,0 +1,98 @@
+#![allow(dead_code)]
+use interpolate_macro::Interpolate;
+
+trait Interpolate: Sized {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self;
+
+    fn interp_weights(perspective: [f32; 3], _screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        Self::interp(perspective[0], perspective[1], perspective[2], a, b, c)
+    }
+}
+
+impl Interpolate for f32 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        l0 * a + l1 * b + l2 * c
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        std::array::from_fn(|i| T::interp(l0, l1, l2, &a[i], &b[i], &c[i]))
+    }
+
+    fn interp_weights(perspective: [f32; 3], screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        std::array::from_fn(|i| T::interp_weights(perspective, screen, &a[i], &b[i], &c[i]))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Float2 {
+    x: f32,
+    y: f32,
+}
+
+impl Interpolate for Float2 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        Float2 {
+            x: f32::interp(l0, l1, l2, &a.x, &b.x, &c.x),
+            y: f32::interp(l0, l1, l2, &a.y, &b.y, &c.y),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Color([u8; 4]);
+
+impl Interpolate for Color {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        Color(std::array::from_fn(|i| {
+            f32::interp(l0, l1, l2, &(a.0[i] as f32), &(b.0[i] as f32), &(c.0[i] as f32)) as u8
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Interpolate)]
+struct Inner {
+    #[interpolate(noperspective)]
+    screen: f32,
+}
+
+#[derive(Clone, Copy, Interpolate)]
+struct Varyings {
+    #[interpolate(smooth)]
+    uv: Float2,
+    color: Color,
+    #[interpolate(noperspective)]
+    screen: f32,
+    #[interpolate(flat)]
+    material: u32,
+    #[interpolate(skip)]
+    scratch: u64,
+    inner: [Inner; 2],
+}
+
+fn main() {
+    let a = Varyings {
+        uv: Float2 { x: 1.0, y: 0.0 },
+        color: Color([255, 0, 0, 255]),
+        screen: 1.0,
+        material: 7,
+        scratch: 9,
+        inner: [Inner { screen: 1.0 }; 2],
+    };
+    let b = Varyings {
+        uv: Float2 { x: 0.0, y: 0.0 },
+        color: Color([0, 0, 0, 255]),
+        screen: 0.0,
+        material: 8,
+        scratch: 9,
+        inner: [Inner { screen: 0.0 }; 2],
+    };
+    let v = Varyings::interp_weights([0.25, 0.75, 0.0], [0.5, 0.5, 0.0], &a, &b, &b);
+    assert_eq!(v.uv.x, 0.25);
+    assert_eq!(v.screen, 0.5);
+    assert_eq!(v.inner[1].screen, 0.5);
+    assert_eq!(v.material, 7);
+    assert_eq!(v.scratch, 0);
+    assert_eq!(v.color.0[0], 63);
+}
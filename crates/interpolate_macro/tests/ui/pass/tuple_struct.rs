@@ -0,0 +1,47 @@
+#![allow(dead_code)]
+use interpolate_macro::Interpolate;
+
+trait Interpolate: Sized {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self;
+
+    fn interp_weights(perspective: [f32; 3], _screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        Self::interp(perspective[0], perspective[1], perspective[2], a, b, c)
+    }
+}
+
+impl Interpolate for f32 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        l0 * a + l1 * b + l2 * c
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Float2 {
+    x: f32,
+    y: f32,
+}
+
+impl Interpolate for Float2 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        Float2 {
+            x: f32::interp(l0, l1, l2, &a.x, &b.x, &c.x),
+            y: f32::interp(l0, l1, l2, &a.y, &b.y, &c.y),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Interpolate)]
+struct Varyings(Float2, f32, #[interpolate(flat)] u32);
+
+#[derive(Clone, Copy, Interpolate)]
+struct Empty;
+
+fn main() {
+    let a = Varyings(Float2 { x: 1.0, y: 0.0 }, 1.0, 3);
+    let b = Varyings(Float2 { x: 0.0, y: 1.0 }, 0.0, 4);
+    let v = Varyings::interp(0.5, 0.5, 0.0, &a, &b, &b);
+    assert_eq!(v.0.x, 0.5);
+    assert_eq!(v.1, 0.5);
+    assert_eq!(v.2, 3);
+    let _ = Empty::interp(1.0, 0.0, 0.0, &Empty, &Empty, &Empty);
+}
@@ -0,0 +1,393 @@
+use crate::math::{Float3, Float4, Matrix4, Quaternion};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: Float3,
+    pub rotation: Quaternion,
+    pub scale: Float3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Float3::zero(),
+            rotation: Quaternion::identity(),
+            scale: Float3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::from_trs(self.translation, self.rotation, self.scale)
+    }
+}
+
+pub struct Pose {
+    pub transforms: Vec<Transform>,
+    pub morph_weights: Vec<Vec<f32>>,
+}
+
+pub struct Skin {
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Matrix4>,
+    pub skeleton: Option<usize>,
+}
+
+impl Skin {
+    // Joint matrices relative to the scene root, indexed by the JOINTS_0 attribute of the skinned
+    // mesh. Skinned vertices end up in scene space, so only the scene's own transform applies after.
+    pub fn joint_matrices(&self, world_transforms: &[Matrix4]) -> Vec<Matrix4> {
+        self.joints
+            .iter()
+            .enumerate()
+            .map(|(i, &joint)| {
+                let inverse_bind = self
+                    .inverse_bind_matrices
+                    .get(i)
+                    .copied()
+                    .unwrap_or_else(Matrix4::identity);
+                world_transforms[joint] * inverse_bind
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+pub enum ChannelOutputs {
+    Translations(Vec<Float3>),
+    Rotations(Vec<Quaternion>),
+    Scales(Vec<Float3>),
+    // Flattened, every keyframe holds one weight per morph target.
+    MorphWeights(Vec<f32>),
+}
+
+pub struct Channel {
+    pub node: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub outputs: ChannelOutputs,
+}
+
+pub struct Animation {
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+    pub duration: f32,
+}
+
+impl Animation {
+    // Overwrites the animated properties of the node transforms, time is clamped to the
+    // keyframe range so callers loop with `time % animation.duration`.
+    pub fn apply(&self, time: f32, pose: &mut Pose) {
+        for channel in &self.channels {
+            let transform = &mut pose.transforms[channel.node];
+            match &channel.outputs {
+                ChannelOutputs::Translations(values) => {
+                    let value = |i: usize| values.get(i).copied();
+                    if let Some(t) = sample(&channel.times, value, channel.interpolation, time) {
+                        transform.translation = t;
+                    }
+                }
+                ChannelOutputs::Rotations(values) => {
+                    let value = |i: usize| values.get(i).copied();
+                    if let Some(r) = sample(&channel.times, value, channel.interpolation, time) {
+                        transform.rotation = r;
+                    }
+                }
+                ChannelOutputs::Scales(values) => {
+                    let value = |i: usize| values.get(i).copied();
+                    if let Some(s) = sample(&channel.times, value, channel.interpolation, time) {
+                        transform.scale = s;
+                    }
+                }
+                ChannelOutputs::MorphWeights(values) => {
+                    let weights = &mut pose.morph_weights[channel.node];
+                    let values_per_keyframe = match channel.interpolation {
+                        Interpolation::CubicSpline => 3 * channel.times.len(),
+                        _ => channel.times.len(),
+                    };
+                    if values_per_keyframe == 0 {
+                        continue;
+                    }
+                    let target_count = values.len() / values_per_keyframe;
+                    weights.resize(target_count, 0.0);
+                    for (target, weight) in weights.iter_mut().enumerate() {
+                        let value = |i: usize| values.get(i * target_count + target).copied();
+                        if let Some(w) = sample(&channel.times, value, channel.interpolation, time) {
+                            *weight = w;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+trait Keyframe: Copy {
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+    fn hermite(v0: Self, out0: Self, v1: Self, in1: Self, t: f32, dt: f32) -> Self;
+}
+
+fn hermite_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        2.0 * t3 - 3.0 * t2 + 1.0,
+        t3 - 2.0 * t2 + t,
+        -2.0 * t3 + 3.0 * t2,
+        t3 - t2,
+    ]
+}
+
+impl Keyframe for f32 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+
+    fn hermite(v0: Self, out0: Self, v1: Self, in1: Self, t: f32, dt: f32) -> Self {
+        let [h00, h10, h01, h11] = hermite_weights(t);
+        h00 * v0 + h10 * dt * out0 + h01 * v1 + h11 * dt * in1
+    }
+}
+
+impl Keyframe for Float3 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        (1.0 - t) * a + t * b
+    }
+
+    fn hermite(v0: Self, out0: Self, v1: Self, in1: Self, t: f32, dt: f32) -> Self {
+        let [h00, h10, h01, h11] = hermite_weights(t);
+        h00 * v0 + (h10 * dt) * out0 + h01 * v1 + (h11 * dt) * in1
+    }
+}
+
+impl Keyframe for Quaternion {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a.slerp(b, t)
+    }
+
+    fn hermite(v0: Self, out0: Self, v1: Self, in1: Self, t: f32, dt: f32) -> Self {
+        let [h00, h10, h01, h11] = hermite_weights(t);
+        let (b0, b1) = (h10 * dt, h11 * dt);
+        Quaternion::new(
+            h00 * v0.x + b0 * out0.x + h01 * v1.x + b1 * in1.x,
+            h00 * v0.y + b0 * out0.y + h01 * v1.y + b1 * in1.y,
+            h00 * v0.z + b0 * out0.z + h01 * v1.z + b1 * in1.z,
+            h00 * v0.w + b0 * out0.w + h01 * v1.w + b1 * in1.w,
+        )
+        .normalize()
+    }
+}
+
+// Returns the keyframe pair surrounding `time` and the normalized position between them.
+fn find_keyframes(times: &[f32], time: f32) -> Option<(usize, usize, f32)> {
+    let last = times.len().checked_sub(1)?;
+    if time <= times[0] {
+        return Some((0, 0, 0.0));
+    }
+    if time >= times[last] {
+        return Some((last, last, 0.0));
+    }
+    let next = times.partition_point(|&t| t <= time);
+    let previous = next - 1;
+    let span = times[next] - times[previous];
+    let t = if span > 0.0 { (time - times[previous]) / span } else { 0.0 };
+    Some((previous, next, t))
+}
+
+// `value(i)` returns the i-th output element, so interleaved outputs can be read in place.
+fn sample<T: Keyframe>(
+    times: &[f32],
+    value: impl Fn(usize) -> Option<T>,
+    interpolation: Interpolation,
+    time: f32,
+) -> Option<T> {
+    let (previous, next, t) = find_keyframes(times, time)?;
+    match interpolation {
+        Interpolation::Step => value(previous),
+        Interpolation::Linear => Some(T::lerp(value(previous)?, value(next)?, t)),
+        Interpolation::CubicSpline => {
+            // Each keyframe stores [in tangent, value, out tangent].
+            let v0 = value(3 * previous + 1)?;
+            if previous == next {
+                return Some(v0);
+            }
+            let out0 = value(3 * previous + 2)?;
+            let in1 = value(3 * next)?;
+            let v1 = value(3 * next + 1)?;
+            Some(T::hermite(v0, out0, v1, in1, t, times[next] - times[previous]))
+        }
+    }
+}
+
+// Vertices without any weight stay where they are instead of collapsing to the origin.
+pub fn skin_matrix(joints: [u16; 4], weights: Float4, joint_matrices: &[Matrix4]) -> Matrix4 {
+    if weights.x + weights.y + weights.z + weights.w == 0.0 {
+        return Matrix4::identity();
+    }
+    let mut result = Matrix4::new();
+    for (joint, weight) in joints.into_iter().zip([weights.x, weights.y, weights.z, weights.w]) {
+        if weight == 0.0 {
+            continue;
+        }
+        let joint_matrix = &joint_matrices[joint as usize];
+        for (r, m) in result.data.iter_mut().zip(joint_matrix.data.iter()) {
+            *r += weight * m;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unweighted_vertices_are_not_skinned() {
+        let joint_matrices = [Matrix4::translate(Float3::new(1.0, 2.0, 3.0))];
+        let skin = skin_matrix([0; 4], Float4::zero(), &joint_matrices);
+        assert_eq!(skin.data, Matrix4::identity().data);
+
+        let skin = skin_matrix([0; 4], Float4::new(1.0, 0.0, 0.0, 0.0), &joint_matrices);
+        assert_eq!(skin.data, joint_matrices[0].data);
+    }
+
+    fn pose() -> Pose {
+        Pose {
+            transforms: vec![Transform::default()],
+            morph_weights: vec![vec![]],
+        }
+    }
+
+    fn translation_at(interpolation: Interpolation, times: &[f32], values: &[Float3], time: f32) -> Float3 {
+        let animation = Animation {
+            name: None,
+            channels: vec![Channel {
+                node: 0,
+                interpolation,
+                times: times.to_vec(),
+                outputs: ChannelOutputs::Translations(values.to_vec()),
+            }],
+            duration: times.last().copied().unwrap_or(0.0),
+        };
+        let mut pose = pose();
+        animation.apply(time, &mut pose);
+        pose.transforms[0].translation
+    }
+
+    fn assert_close(a: Float3, b: Float3) {
+        assert!((a - b).length() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    fn x(value: f32) -> Float3 {
+        Float3::new(value, 0.0, 0.0)
+    }
+
+    #[test]
+    fn step_holds_the_previous_keyframe() {
+        let times = [0.0, 1.0, 2.0];
+        let values = [x(0.0), x(10.0), x(20.0)];
+        assert_close(translation_at(Interpolation::Step, &times, &values, 0.0), x(0.0));
+        assert_close(translation_at(Interpolation::Step, &times, &values, 0.99), x(0.0));
+        assert_close(translation_at(Interpolation::Step, &times, &values, 1.0), x(10.0));
+        assert_close(translation_at(Interpolation::Step, &times, &values, 1.5), x(10.0));
+    }
+
+    #[test]
+    fn linear_interpolates_between_keyframes() {
+        let times = [0.0, 1.0, 3.0];
+        let values = [x(0.0), x(10.0), x(20.0)];
+        assert_close(translation_at(Interpolation::Linear, &times, &values, 0.25), x(2.5));
+        assert_close(translation_at(Interpolation::Linear, &times, &values, 2.0), x(15.0));
+    }
+
+    #[test]
+    fn linear_rotation_slerps() {
+        let animation = Animation {
+            name: None,
+            channels: vec![Channel {
+                node: 0,
+                interpolation: Interpolation::Linear,
+                times: vec![0.0, 1.0],
+                outputs: ChannelOutputs::Rotations(vec![
+                    Quaternion::identity(),
+                    Quaternion::from_axis_angle(Float3::new(0.0, 1.0, 0.0), 2.0),
+                ]),
+            }],
+            duration: 1.0,
+        };
+        let mut pose = pose();
+        animation.apply(0.5, &mut pose);
+        let expected = Quaternion::from_axis_angle(Float3::new(0.0, 1.0, 0.0), 1.0);
+        assert!(pose.transforms[0].rotation.dot(expected).abs() > 1.0 - 1e-5);
+    }
+
+    #[test]
+    fn cubic_spline_reads_in_tangent_value_out_tangent() {
+        let times = [0.0, 2.0];
+        // The in tangent of the first and the out tangent of the last keyframe never contribute.
+        let flat = [x(100.0), x(0.0), x(0.0), x(0.0), x(2.0), x(-100.0)];
+        // Hermite basis at t = 0.25: h01 = 0.15625.
+        assert_close(translation_at(Interpolation::CubicSpline, &times, &flat, 0.5), x(0.3125));
+
+        // Tangents are scaled by the keyframe spacing: h10 = 0.140625, h11 = -0.046875.
+        let sloped = [x(100.0), x(0.0), x(3.0), x(-1.0), x(2.0), x(-100.0)];
+        let expected = 0.3125 + 0.140625 * 2.0 * 3.0 + 0.046875 * 2.0;
+        assert_close(translation_at(Interpolation::CubicSpline, &times, &sloped, 0.5), x(expected));
+
+        assert_close(translation_at(Interpolation::CubicSpline, &times, &sloped, 2.0), x(2.0));
+    }
+
+    #[test]
+    fn sampling_clamps_outside_the_keyframe_range() {
+        let times = [1.0, 2.0];
+        let values = [x(5.0), x(7.0)];
+        for interpolation in [Interpolation::Step, Interpolation::Linear] {
+            assert_close(translation_at(interpolation, &times, &values, 0.0), x(5.0));
+            assert_close(translation_at(interpolation, &times, &values, 9.0), x(7.0));
+        }
+
+        let cubic = [x(1.0), x(5.0), x(1.0), x(1.0), x(7.0), x(1.0)];
+        assert_close(translation_at(Interpolation::CubicSpline, &times, &cubic, 0.0), x(5.0));
+        assert_close(translation_at(Interpolation::CubicSpline, &times, &cubic, 9.0), x(7.0));
+    }
+
+    #[test]
+    fn morph_weights_are_read_per_target() {
+        let weights_at = |interpolation: Interpolation, values: Vec<f32>, time: f32| {
+            let animation = Animation {
+                name: None,
+                channels: vec![Channel {
+                    node: 0,
+                    interpolation,
+                    times: vec![0.0, 1.0],
+                    outputs: ChannelOutputs::MorphWeights(values),
+                }],
+                duration: 1.0,
+            };
+            let mut pose = pose();
+            animation.apply(time, &mut pose);
+            pose.morph_weights[0].clone()
+        };
+
+        // Two targets, keyframes [0.0, 1.0] and [1.0, 0.5].
+        let linear = weights_at(Interpolation::Linear, vec![0.0, 1.0, 1.0, 0.5], 0.5);
+        assert_eq!(linear, vec![0.5, 0.75]);
+
+        // Cubic keyframes hold in tangents, values and out tangents for every target in turn.
+        let cubic = vec![9.0, 9.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.5, 9.0, 9.0];
+        assert_eq!(weights_at(Interpolation::CubicSpline, cubic.clone(), 0.0), vec![0.0, 1.0]);
+        assert_eq!(weights_at(Interpolation::CubicSpline, cubic, 1.0), vec![1.0, 0.5]);
+    }
+
+    #[test]
+    fn empty_channels_leave_the_pose_untouched() {
+        assert_close(translation_at(Interpolation::Linear, &[], &[], 1.0), Float3::zero());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::math::{Float3, Matrix4};
+use crate::window::Window;
+use sdl3::keyboard::Scancode;
+use sdl3::mouse::MouseButton;
+
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+pub enum CameraController {
+    Orbit {
+        target: Float3,
+        distance: f32,
+        yaw: f32,
+        pitch: f32,
+    },
+    Fly {
+        position: Float3,
+        yaw: f32,
+        pitch: f32,
+    },
+}
+
+pub struct Camera {
+    pub controller: CameraController,
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    pub move_speed: f32,
+    pub look_sensitivity: f32,
+    pub zoom_speed: f32,
+}
+
+impl Camera {
+    pub fn orbit(target: Float3, distance: f32) -> Self {
+        Self::with_controller(CameraController::Orbit {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+        })
+    }
+
+    pub fn fly(position: Float3) -> Self {
+        Self::with_controller(CameraController::Fly {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+        })
+    }
+
+    fn with_controller(controller: CameraController) -> Self {
+        Self {
+            controller,
+            fov_y: std::f32::consts::PI / 3.0,
+            near: 0.01,
+            far: 100.0,
+            move_speed: 5.0,
+            look_sensitivity: 0.005,
+            zoom_speed: 0.1,
+        }
+    }
+
+    // Orbit: left drag rotates, right drag pans, wheel zooms.
+    // Fly: right drag looks around, WASD moves, Q/E moves down/up, shift speeds up.
+    pub fn update(&mut self, window: &Window, dt: f32) {
+        let (dx, dy) = window.get_mouse_delta();
+        let look = (dx * self.look_sensitivity, dy * self.look_sensitivity);
+        let wheel = window.get_wheel_delta();
+        let forward = self.forward();
+        let right = self.right();
+        let up = right.cross(forward);
+
+        match &mut self.controller {
+            CameraController::Orbit {
+                target,
+                distance,
+                yaw,
+                pitch,
+            } => {
+                if window.is_mouse_button_down(MouseButton::Left) {
+                    *yaw -= look.0;
+                    *pitch = (*pitch - look.1).clamp(-MAX_PITCH, MAX_PITCH);
+                }
+                if window.is_mouse_button_down(MouseButton::Right) {
+                    let pan_scale = *distance * self.look_sensitivity * 0.2;
+                    *target = *target - (dx * pan_scale) * right + (dy * pan_scale) * up;
+                }
+                *distance = (*distance * (1.0 - wheel * self.zoom_speed)).max(self.near);
+            }
+            CameraController::Fly {
+                position,
+                yaw,
+                pitch,
+            } => {
+                if window.is_mouse_button_down(MouseButton::Right) {
+                    *yaw -= look.0;
+                    *pitch = (*pitch - look.1).clamp(-MAX_PITCH, MAX_PITCH);
+                }
+
+                let mut direction = Float3::zero();
+                let bindings = [
+                    (Scancode::W, forward),
+                    (Scancode::S, -1.0 * forward),
+                    (Scancode::D, right),
+                    (Scancode::A, -1.0 * right),
+                    (Scancode::E, Float3::new(0.0, 1.0, 0.0)),
+                    (Scancode::Q, Float3::new(0.0, -1.0, 0.0)),
+                ];
+                for (key, axis) in bindings {
+                    if window.is_key_down(key) {
+                        direction = direction + axis;
+                    }
+                }
+
+                if direction.length_squared() > 0.0 {
+                    let mut speed = self.move_speed;
+                    if window.is_key_down(Scancode::LShift) {
+                        speed *= 4.0;
+                    }
+                    *position = *position + (speed * dt) * direction.normalize();
+                }
+            }
+        }
+    }
+
+    pub fn forward(&self) -> Float3 {
+        let (yaw, pitch) = match self.controller {
+            CameraController::Orbit { yaw, pitch, .. } => (yaw, pitch),
+            CameraController::Fly { yaw, pitch, .. } => (yaw, pitch),
+        };
+        Float3::new(
+            -yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            -yaw.cos() * pitch.cos(),
+        )
+    }
+
+    pub fn right(&self) -> Float3 {
+        self.forward().cross(Float3::new(0.0, 1.0, 0.0)).normalize()
+    }
+
+    pub fn position(&self) -> Float3 {
+        match self.controller {
+            CameraController::Orbit {
+                target, distance, ..
+            } => target - distance * self.forward(),
+            CameraController::Fly { position, .. } => position,
+        }
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        let position = self.position();
+        Matrix4::look_at(
+            position,
+            position + self.forward(),
+            Float3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Matrix4 {
+        Matrix4::perspective(self.near, self.far, self.fov_y, aspect_ratio)
+    }
+
+    pub fn view_projection_matrix(&self, aspect_ratio: f32) -> Matrix4 {
+        self.projection_matrix(aspect_ratio) * self.view_matrix()
+    }
+}
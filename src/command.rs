@@ -1,4 +1,6 @@
-use crate::image_view::{DepthBuffer, DepthTest, RenderTarget};
+use crate::framebuffer::{Attachment, Attachments, Framebuffer};
+use crate::format::PixelFormat;
+use crate::image_view::{DepthBuffer, DepthTest, Image};
 use crate::math;
 use crate::math::{Color, Float3, Float4, Interpolate};
 use crate::viewport::Viewport;
@@ -32,9 +34,106 @@ pub struct Command<'a> {
     line_color: Color,
 }
 
-pub struct Shader<VertexInput, VertexOutput, FragmentInput> {
-    pub vertex_shader: Box<dyn Fn(u32, &VertexInput) -> (VertexOutput, Float4)>,
-    pub fragment_shader: Box<dyn Fn(&VertexOutput, &FragmentInput) -> Color>,
+// Draw calls are generic over the shader, so both stages are monomorphised and inlined into
+// the rasterizer loops. Returning None from the fragment stage discards the fragment, `Output` is
+// written to the framebuffer attachments, e.g. `Color` or a tuple for multiple render targets.
+pub trait Shader {
+    type VertexInput;
+    type Varyings: Interpolate;
+    type Uniforms;
+    type Output;
+
+    fn vertex(
+        &self,
+        vertex_index: u32,
+        input: &Self::VertexInput,
+        uniforms: &Self::Uniforms,
+    ) -> (Self::Varyings, Float4);
+
+    fn fragment(
+        &self,
+        varyings: &Self::Varyings,
+        builtins: &FragmentBuiltins,
+        uniforms: &Self::Uniforms,
+    ) -> Option<Self::Output>;
+
+    // Capabilities the rasterizer uses to choose between early and late depth testing. Shaders that
+    // never return None opt in to early depth writes by clearing MAY_DISCARD.
+    const MAY_DISCARD: bool = true;
+    const WRITES_DEPTH: bool = false;
+
+    // Shaders that set WRITES_DEPTH override this, the default keeps the interpolated depth.
+    fn fragment_with_depth(
+        &self,
+        varyings: &Self::Varyings,
+        builtins: &FragmentBuiltins,
+        uniforms: &Self::Uniforms,
+    ) -> Option<(Self::Output, f32)> {
+        self.fragment(varyings, builtins, uniforms)
+            .map(|output| (output, builtins.frag_coord.z))
+    }
+}
+
+pub struct FragmentBuiltins {
+    // Pixel center in window coordinates, z is the fragment depth and w is 1 / clip w.
+    pub frag_coord: Float4,
+    pub front_facing: bool,
+    // Index of the triangle within the draw call, triangles split by clipping share it.
+    pub primitive_id: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DepthOrder {
+    // Test and write before shading, fragments that fail never reach the shader.
+    Early,
+    // Test before shading, write only once the fragment survives discard.
+    EarlyTestLateWrite,
+    // Test and write after shading against the depth the shader produced.
+    Late,
+}
+
+impl DepthOrder {
+    pub const fn for_shader<S: Shader>() -> Self {
+        if S::WRITES_DEPTH {
+            DepthOrder::Late
+        } else if S::MAY_DISCARD {
+            DepthOrder::EarlyTestLateWrite
+        } else {
+            DepthOrder::Early
+        }
+    }
+}
+
+pub type VertexFn<VertexInput, Varyings, Uniforms> =
+    Box<dyn Fn(u32, &VertexInput, &Uniforms) -> (Varyings, Float4)>;
+pub type FragmentFn<Varyings, Uniforms, Output> =
+    Box<dyn Fn(&Varyings, &FragmentBuiltins, &Uniforms) -> Option<Output>>;
+
+// Adapter for quick experiments, every vertex and fragment goes through a dynamic call.
+pub struct ClosureShader<VertexInput, Varyings, Uniforms, Output = Color> {
+    pub vertex_shader: VertexFn<VertexInput, Varyings, Uniforms>,
+    pub fragment_shader: FragmentFn<Varyings, Uniforms, Output>,
+}
+
+impl<VertexInput, Varyings, Uniforms, Output> Shader
+    for ClosureShader<VertexInput, Varyings, Uniforms, Output>
+where
+    Varyings: Interpolate,
+{
+    type VertexInput = VertexInput;
+    type Varyings = Varyings;
+    type Uniforms = Uniforms;
+    type Output = Output;
+
+    const MAY_DISCARD: bool = true;
+
+    fn vertex(&self, vertex_index: u32, input: &VertexInput, uniforms: &Uniforms) -> (Varyings, Float4) {
+        (self.vertex_shader)(vertex_index, input, uniforms)
+    }
+
+    fn fragment(&self, varyings: &Varyings, builtins: &FragmentBuiltins, uniforms: &Uniforms) -> Option<Output> {
+        (self.fragment_shader)(varyings, builtins, uniforms)
+    }
 }
 
 impl<'a> Command<'a> {
@@ -91,7 +190,7 @@ impl<'a> Command<'a> {
         self.indices = Some(indices);
     }
 
-    pub fn clear_render_target(&mut self, image: &mut RenderTarget, color: Float4) {
+    pub fn clear_render_target<P: PixelFormat>(&mut self, image: &mut Image<P>, color: Float4) {
         image.clear_image(color);
     }
 
@@ -99,42 +198,35 @@ impl<'a> Command<'a> {
         image.clear_image(value);
     }
 
-    pub fn draw<VertexInput, VertexOutput, FragmentInput>(
+    pub fn draw<S: Shader, C: Attachments<S::Output>>(
         &mut self,
-        render_target: &mut RenderTarget,
-        depth_buffer: &mut DepthBuffer,
-        shader: &Shader<VertexInput, VertexOutput, FragmentInput>,
-        vertex_input: &VertexInput,
-        fragment_input: &FragmentInput,
-    ) where
-        VertexOutput: Interpolate,
-    {
+        framebuffer: &mut Framebuffer<C>,
+        shader: &S,
+        vertex_input: &S::VertexInput,
+        uniforms: &S::Uniforms,
+    ) {
         let positions = self.positions.unwrap();
         for vertex_index in (0..positions.len() - 2).step_by(3) {
             let i0 = vertex_index;
             let i1 = vertex_index + 1;
             let i2 = vertex_index + 2;
             self.rasterize_triangle(
-                render_target,
-                depth_buffer,
+                framebuffer,
                 shader,
                 vertex_input,
-                fragment_input,
-                [i0, i1, i2],
+                uniforms,
+                ((vertex_index / 3) as u32, [i0, i1, i2]),
             );
         }
     }
 
-    pub fn draw_indexed<VertexInput, VertexOutput, FragmentInput>(
+    pub fn draw_indexed<S: Shader, C: Attachments<S::Output>>(
         &mut self,
-        render_target: &mut RenderTarget,
-        depth_buffer: &mut DepthBuffer,
-        shader: &Shader<VertexInput, VertexOutput, FragmentInput>,
-        vertex_input: &VertexInput,
-        fragment_input: &FragmentInput,
-    ) where
-        VertexOutput: Interpolate,
-    {
+        framebuffer: &mut Framebuffer<C>,
+        shader: &S,
+        vertex_input: &S::VertexInput,
+        uniforms: &S::Uniforms,
+    ) {
         let indices = self.indices.unwrap();
         for vertex_index in (0..indices.len() - 2).step_by(3) {
             let mut i0 = vertex_index;
@@ -144,36 +236,39 @@ impl<'a> Command<'a> {
             i1 = indices[i1] as usize;
             i2 = indices[i2] as usize;
             self.rasterize_triangle(
-                render_target,
-                depth_buffer,
+                framebuffer,
                 shader,
                 vertex_input,
-                fragment_input,
-                [i0, i1, i2],
+                uniforms,
+                ((vertex_index / 3) as u32, [i0, i1, i2]),
             );
         }
     }
 
-    fn rasterize_triangle<VertexInput, VertexOutput, FragmentInput>(
+    fn rasterize_triangle<S: Shader, C: Attachments<S::Output>>(
         &mut self,
-        render_target: &mut RenderTarget,
-        depth_buffer: &mut DepthBuffer,
-        shader: &Shader<VertexInput, VertexOutput, FragmentInput>,
-        vertex_input: &VertexInput,
-        fragment_input: &FragmentInput,
-        triangle_indices: [usize; 3],
-    ) where
-        VertexOutput: Interpolate,
-    {
+        framebuffer: &mut Framebuffer<C>,
+        shader: &S,
+        vertex_input: &S::VertexInput,
+        uniforms: &S::Uniforms,
+        primitive: (u32, [usize; 3]),
+    ) {
+        let (primitive_id, triangle_indices) = primitive;
         let (vertex_output0, position0) =
-            (shader.vertex_shader)(triangle_indices[0] as u32, &vertex_input);
+            shader.vertex(triangle_indices[0] as u32, vertex_input, uniforms);
         let (mut vertex_output1, position1) =
-            (shader.vertex_shader)(triangle_indices[1] as u32, &vertex_input);
+            shader.vertex(triangle_indices[1] as u32, vertex_input, uniforms);
         let (mut vertex_output2, position2) =
-            (shader.vertex_shader)(triangle_indices[2] as u32, &vertex_input);
+            shader.vertex(triangle_indices[2] as u32, vertex_input, uniforms);
 
         let (clipped_vertices, count) = clip_vertices([position0, position1, position2]);
 
+        // Wireframes never run the fragment shader.
+        let depth_order = match self.fill_mode {
+            FillMode::Solid => DepthOrder::for_shader::<S>(),
+            FillMode::Wireframe => DepthOrder::Early,
+        };
+
         for triangle in clipped_vertices[..count as usize].chunks_exact(3) {
             let mut v0 = triangle[0];
             let mut v1 = triangle[1];
@@ -189,6 +284,8 @@ impl<'a> Command<'a> {
 
             let mut det012 = (v1 - v0).det2d(v2 - v0);
             let ccw = det012 < 0.0;
+            // The viewport flips y, so counter-clockwise in NDC is clockwise on screen.
+            let front_facing = ccw;
 
             match self.cull_mode {
                 CullMode::None => {
@@ -213,10 +310,11 @@ impl<'a> Command<'a> {
                 }
             }
 
+            let (width, height) = framebuffer.size();
             let mut x_min: i32 = self.viewport.x_min.max(0);
-            let mut x_max: i32 = self.viewport.x_max.min(render_target.width as i32) - 1;
+            let mut x_max: i32 = self.viewport.x_max.min(width as i32) - 1;
             let mut y_min: i32 = self.viewport.y_min.max(0);
-            let mut y_max: i32 = self.viewport.y_max.min(render_target.height as i32) - 1;
+            let mut y_max: i32 = self.viewport.y_max.min(height as i32) - 1;
 
             let tri_x_min = v0.x.floor().min(v1.x.floor()).min(v2.x.floor()) as i32;
 
@@ -239,79 +337,118 @@ impl<'a> Command<'a> {
                     let det20p = (v0 - v2).det2d(p - v2);
 
                     if det01p > 0.0 && det12p > 0.0 && det20p > 0.0 {
-                        let mut l0 = (v1 - p).det2d(v2 - p) / det012 / v0.w;
-                        let mut l1 = (v2 - p).det2d(v0 - p) / det012 / v1.w;
-                        let mut l2 = (v0 - p).det2d(v1 - p) / det012 / v2.w;
+                        let s0 = (v1 - p).det2d(v2 - p) / det012;
+                        let s1 = (v2 - p).det2d(v0 - p) / det012;
+                        let s2 = (v0 - p).det2d(v1 - p) / det012;
 
+                        let mut l0 = s0 / v0.w;
+                        let mut l1 = s1 / v1.w;
+                        let mut l2 = s2 / v2.w;
+
+                        // Sum of the perspective weights, the interpolated 1 / w.
                         let l_sum = l0 + l1 + l2;
 
                         l0 /= l_sum;
                         l1 /= l_sum;
                         l2 /= l_sum;
 
-                        let old_depth = depth_buffer.get_pixel(x as u32, y as u32);
+                        let old_depth = framebuffer
+                            .depth
+                            .as_deref_mut()
+                            .map(|depth_buffer| depth_buffer.get_pixel(x as u32, y as u32));
 
                         let z = l0 * v0.z + l1 * v1.z + l2 * v2.z;
 
-                        if passed_depth_test(self.depth_state.test, z, old_depth) {
-                            if self.depth_state.write {
-                                depth_buffer.set_pixel(x as u32, y as u32, z);
-                            }
-                        } else {
+                        if depth_order != DepthOrder::Late
+                            && !self.passed_depth_test(z, old_depth)
+                        {
                             continue;
                         }
+                        if depth_order == DepthOrder::Early {
+                            self.write_depth(framebuffer, (x as u32, y as u32), z);
+                        }
 
-                        match self.fill_mode {
-                            FillMode::Solid => self.fill_triangle(
-                                render_target,
-                                &shader.fragment_shader,
-                                fragment_input,
-                                (l0, l1, l2),
-                                (&vertex_output0, &vertex_output1, &vertex_output2),
-                                (x as u32, y as u32)
-                            ),
-                            FillMode::Wireframe => self.wireframe_triangle(
-                                render_target,
-                                (det01p, det12p, det20p),
-                                (v0, v1, v2),
-                                (x as u32, y as u32)
-                            ),
+                        let depth = match self.fill_mode {
+                            FillMode::Solid => {
+                                // Discarded fragments must not occlude what is drawn behind them.
+                                let builtins = FragmentBuiltins {
+                                    frag_coord: Float4::new(p.x, p.y, z, l_sum),
+                                    front_facing,
+                                    primitive_id,
+                                };
+                                let Some((output, depth)) = self.shade_fragment(
+                                    shader,
+                                    uniforms,
+                                    &builtins,
+                                    ([l0, l1, l2], [s0, s1, s2]),
+                                    (&vertex_output0, &vertex_output1, &vertex_output2),
+                                ) else {
+                                    continue;
+                                };
+                                if depth_order == DepthOrder::Late
+                                    && !self.passed_depth_test(depth, old_depth)
+                                {
+                                    continue;
+                                }
+                                framebuffer.colors.write(x as u32, y as u32, output);
+                                depth
+                            }
+                            FillMode::Wireframe => {
+                                self.wireframe_triangle(
+                                    &mut framebuffer.colors,
+                                    (det01p, det12p, det20p),
+                                    (v0, v1, v2),
+                                    (x as u32, y as u32)
+                                );
+                                z
+                            }
                         };
+
+                        if depth_order != DepthOrder::Early {
+                            self.write_depth(framebuffer, (x as u32, y as u32), depth);
+                        }
                     }
                 }
             }
         }
     }
 
-    fn fill_triangle<VertexOutput, FragmentInput>(
+    // Without a depth attachment every fragment passes.
+    fn passed_depth_test(&self, depth: f32, old_depth: Option<f32>) -> bool {
+        old_depth.is_none_or(|old_depth| passed_depth_test(self.depth_state.test, depth, old_depth))
+    }
+
+    fn write_depth<C>(&self, framebuffer: &mut Framebuffer<C>, screen_coords: (u32, u32), depth: f32) {
+        if let Some(depth_buffer) = framebuffer.depth.as_deref_mut()
+            && self.depth_state.write
+        {
+            depth_buffer.set_pixel(screen_coords.0, screen_coords.1, depth);
+        }
+    }
+
+    fn shade_fragment<S: Shader>(
         &mut self,
-        render_target: &mut RenderTarget,
-        fragment_shader: &Box<dyn Fn(&VertexOutput, &FragmentInput) -> Color>,
-        fragment_input: &FragmentInput,
-        triangle_areas: (f32, f32, f32),
-        vertex_output: (&VertexOutput, &VertexOutput, &VertexOutput),
-        screen_coords: (u32, u32)
-    )
-    where
-        VertexOutput: Interpolate,
-    {
-        let (l0, l1, l2) = triangle_areas;
+        shader: &S,
+        uniforms: &S::Uniforms,
+        builtins: &FragmentBuiltins,
+        weights: ([f32; 3], [f32; 3]),
+        vertex_output: (&S::Varyings, &S::Varyings, &S::Varyings),
+    ) -> Option<(S::Output, f32)> {
+        let (perspective, screen) = weights;
         let (vertex_output0, vertex_output1, vertex_output2) = vertex_output;
-        let interpolated_output = VertexOutput::interp(
-            l0,
-            l1,
-            l2,
-            &vertex_output0,
-            &vertex_output1,
-            &vertex_output2,
+        let interpolated_output = S::Varyings::interp_weights(
+            perspective,
+            screen,
+            vertex_output0,
+            vertex_output1,
+            vertex_output2,
         );
-        let color = fragment_shader(&interpolated_output, &fragment_input);
-        render_target.set_pixel(screen_coords.0, screen_coords.1, color);
+        shader.fragment_with_depth(&interpolated_output, builtins, uniforms)
     }
 
     fn wireframe_triangle(
         &mut self,
-        render_target: &mut RenderTarget,
+        attachments: &mut impl Attachment,
         determinants: (f32, f32, f32),
         clipped_vertices: (Float4, Float4, Float4),
         screen_coords: (u32, u32)
@@ -332,7 +469,7 @@ impl<'a> Command<'a> {
 
         let min = e0_normalized.min(e1_normalized).min(e2_normalized);
         if min < self.line_width {
-            render_target.set_pixel(screen_coords.0, screen_coords.1, self.line_color);
+            attachments.write_color(screen_coords.0, screen_coords.1, self.line_color);
         }
     }
 }
@@ -458,9 +595,9 @@ fn clip_vertices(vertices: [Float4; 3]) -> ([Float4; 12], u32) {
         Float4::new(0.0, 0.0, -1.0, 1.0),
     ];
 
-    let mut clip_count = 0;
     for equation in equations.into_iter() {
         let mut output: [Float4; 12] = [Float4::default(); 12];
+        let mut clip_count = 0;
         for triangle in input[0..count as usize].chunks_exact(3) {
             clip_triangle_against_plane(triangle, equation, &mut output, &mut clip_count);
         }
@@ -468,7 +605,7 @@ fn clip_vertices(vertices: [Float4; 3]) -> ([Float4; 12], u32) {
         input = output;
     }
 
-    (input, clip_count)
+    (input, count)
 }
 
 const fn passed_depth_test(depth_test: DepthTest, value: f32, reference: f32) -> bool {
@@ -483,3 +620,23 @@ const fn passed_depth_test(depth_test: DepthTest, value: f32, reference: f32) ->
         DepthTest::NotEqual => value != reference,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipping_against_near_and_far_keeps_vertices_inside() {
+        // One vertex in front of the near plane and one behind the far plane, so both planes split.
+        let (clipped, count) = clip_vertices([
+            Float4::new(0.0, 0.0, -0.5, 1.0),
+            Float4::new(1.0, 0.0, 0.5, 1.0),
+            Float4::new(0.0, 1.0, 1.5, 1.0),
+        ]);
+        assert_eq!(count, 12);
+        for vertex in &clipped[..count as usize] {
+            assert_eq!(vertex.w, 1.0);
+            assert!((0.0..=1.0).contains(&vertex.z), "{vertex:?}");
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::command::{FragmentBuiltins, Shader};
+use crate::image_view::{Image, RenderTargetRgba32F};
+use crate::math::{self, Float3, Float4, Matrix4};
+use crate::meshes::Mesh;
+use std::f32::consts::PI;
+use std::path::Path;
+
+// Faces follow the usual +X, -X, +Y, -Y, +Z, -Z order, with each face image seen from the inside
+// of the cube and its first row at the top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    // The direction through (u, v) in [0, 1] on this face, not normalized.
+    pub fn direction(self, u: f32, v: f32) -> Float3 {
+        let (s, t) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        match self {
+            CubeFace::PositiveX => Float3::new(1.0, -t, -s),
+            CubeFace::NegativeX => Float3::new(-1.0, -t, s),
+            CubeFace::PositiveY => Float3::new(s, 1.0, t),
+            CubeFace::NegativeY => Float3::new(s, -1.0, -t),
+            CubeFace::PositiveZ => Float3::new(s, -t, 1.0),
+            CubeFace::NegativeZ => Float3::new(-s, -t, -1.0),
+        }
+    }
+
+    // The face a direction points at and the (u, v) it hits, the inverse of `direction`.
+    pub fn from_direction(direction: Float3) -> (CubeFace, f32, f32) {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+        let (face, s, t, major) = if ax >= ay && ax >= az {
+            if x > 0.0 {
+                (CubeFace::PositiveX, -z, -y, ax)
+            } else {
+                (CubeFace::NegativeX, z, -y, ax)
+            }
+        } else if ay >= az {
+            if y > 0.0 {
+                (CubeFace::PositiveY, x, z, ay)
+            } else {
+                (CubeFace::NegativeY, x, -z, ay)
+            }
+        } else if z > 0.0 {
+            (CubeFace::PositiveZ, x, -y, az)
+        } else {
+            (CubeFace::NegativeZ, -x, -y, az)
+        };
+        let major = major.max(f32::MIN_POSITIVE);
+        (face, 0.5 * (s / major + 1.0), 0.5 * (t / major + 1.0))
+    }
+}
+
+// A linear HDR environment, one square RGBA32F image per face.
+pub struct CubeMap {
+    pub size: u32,
+    pub faces: [RenderTargetRgba32F; 6],
+}
+
+impl CubeMap {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            faces: std::array::from_fn(|_| Image::new(size, size)),
+        }
+    }
+
+    // Evaluates `f` for the direction through every texel center, e.g. for procedural skies.
+    pub fn from_fn(size: u32, f: impl Fn(Float3) -> Float4) -> Self {
+        let mut cube_map = Self::new(size);
+        for (face, image) in CubeFace::ALL.into_iter().zip(&mut cube_map.faces) {
+            for y in 0..size {
+                for x in 0..size {
+                    let u = (x as f32 + 0.5) / size as f32;
+                    let v = (y as f32 + 0.5) / size as f32;
+                    image.set_pixel(x, y, f(face.direction(u, v).normalize()));
+                }
+            }
+        }
+        cube_map
+    }
+
+    // Six square images of equal size, given in `CubeFace::ALL` order.
+    pub fn from_files(paths: [&Path; 6]) -> Self {
+        let faces = paths.map(RenderTargetRgba32F::from_file);
+        let size = faces[0].width;
+        for (face, path) in faces.iter().zip(paths) {
+            assert!(
+                face.width == size && face.height == size,
+                "Cube map face {} must be {size}x{size}",
+                path.display()
+            );
+        }
+        Self { size, faces }
+    }
+
+    // Resamples a latitude-longitude panorama, u wraps around +Y and v runs from +Y down to -Y.
+    pub fn from_equirectangular(panorama: &RenderTargetRgba32F, size: u32) -> Self {
+        Self::from_fn(size, |direction| {
+            let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+            let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+            sample_bilinear(
+                panorama,
+                u * panorama.width as f32,
+                v * panorama.height as f32,
+                true,
+            )
+        })
+    }
+
+    pub fn load_equirectangular(path: &Path, size: u32) -> Self {
+        Self::from_equirectangular(&RenderTargetRgba32F::from_file(path), size)
+    }
+
+    // Half resolution copy, each texel averaging a 2x2 block of its parent.
+    pub fn downsample(&self) -> Self {
+        let size = (self.size / 2).max(1);
+        let mut cube_map = Self::new(size);
+        for (target, source) in cube_map.faces.iter_mut().zip(&self.faces) {
+            for y in 0..size {
+                for x in 0..size {
+                    let (x0, y0) = ((2 * x).min(self.size - 1), (2 * y).min(self.size - 1));
+                    let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+                    let sum = source.get_linear(x0, y0)
+                        + source.get_linear(x1, y0)
+                        + source.get_linear(x0, y1)
+                        + source.get_linear(x1, y1);
+                    target.set_pixel(x, y, 0.25 * sum);
+                }
+            }
+        }
+        cube_map
+    }
+
+    // Halves while the source is at least twice `size`, then resamples to the exact size.
+    pub fn resample(&self, size: u32) -> Self {
+        assert!(size > 0, "Cube map size must be positive");
+        if self.size >= 2 * size {
+            return self.downsample().resample(size);
+        }
+        if self.size == size {
+            return Self {
+                size,
+                faces: std::array::from_fn(|i| Image {
+                    pixels: self.faces[i].pixels.clone(),
+                    width: size,
+                    height: size,
+                }),
+            };
+        }
+        Self::from_fn(size, |direction| self.sample(direction))
+    }
+
+    // Bilinear lookup of the radiance arriving from `direction`, which need not be normalized.
+    pub fn sample(&self, direction: Float3) -> Float4 {
+        let (face, u, v) = CubeFace::from_direction(direction);
+        sample_bilinear(
+            &self.faces[face as usize],
+            u * self.size as f32,
+            v * self.size as f32,
+            false,
+        )
+    }
+
+    // Mirror reflection for a surface with `normal` seen along `view_dir`, from the eye outwards.
+    pub fn sample_reflection(&self, view_dir: Float3, normal: Float3) -> Float4 {
+        self.sample(math::reflect(view_dir, normal.normalize()))
+    }
+}
+
+// Pixel centers sit at half integers. Faces clamp at their edges, panoramas wrap horizontally.
+fn sample_bilinear(image: &RenderTargetRgba32F, x: f32, y: f32, wrap_x: bool) -> Float4 {
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let fetch = |x: i32, y: i32| {
+        let x = if wrap_x {
+            x.rem_euclid(image.width as i32)
+        } else {
+            x.clamp(0, image.width as i32 - 1)
+        };
+        let y = y.clamp(0, image.height as i32 - 1);
+        image.pixels[(y as u32 * image.width + x as u32) as usize]
+    };
+    let (x0, y0) = (x0 as i32, y0 as i32);
+    let top = (1.0 - tx) * fetch(x0, y0) + tx * fetch(x0 + 1, y0);
+    let bottom = (1.0 - tx) * fetch(x0, y0 + 1) + tx * fetch(x0 + 1, y0 + 1);
+    (1.0 - ty) * top + ty * bottom
+}
+
+pub struct SkyboxUniforms {
+    pub view: Matrix4,
+    pub projection: Matrix4,
+}
+
+// Draws a cube around the camera at the far plane. Meant to run after the opaque geometry with
+// `DepthTest::LessOrEqual`, depth writes off and no culling, so only uncovered pixels are shaded.
+pub struct SkyboxShader<'a> {
+    pub cube_map: &'a CubeMap,
+}
+
+impl Shader for SkyboxShader<'_> {
+    type VertexInput = Mesh;
+    type Varyings = Float3;
+    type Uniforms = SkyboxUniforms;
+    type Output = Float4;
+
+    const MAY_DISCARD: bool = false;
+
+    fn vertex(&self, vertex_index: u32, mesh: &Mesh, uniforms: &SkyboxUniforms) -> (Float3, Float4) {
+        let direction = mesh.positions[vertex_index as usize];
+        // As a vector only the camera rotation applies, the sky is infinitely far away. Depth is
+        // pinned just inside the far plane so interpolation error can't push it past 1.
+        let position = uniforms.projection * (uniforms.view * direction.as_vector());
+        let depth = position.w * (1.0 - 1e-6);
+        (direction, Float4::new(position.x, position.y, depth, position.w))
+    }
+
+    fn fragment(
+        &self,
+        direction: &Float3,
+        _builtins: &FragmentBuiltins,
+        _uniforms: &SkyboxUniforms,
+    ) -> Option<Float4> {
+        let radiance = self.cube_map.sample(*direction);
+        Some(Float4::new(radiance.x, radiance.y, radiance.z, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_direction_inverts_direction() {
+        for face in CubeFace::ALL {
+            for i in 0..9 {
+                for j in 0..9 {
+                    let (u, v) = (0.05 + 0.1125 * i as f32, 0.05 + 0.1125 * j as f32);
+                    let (hit, hit_u, hit_v) = CubeFace::from_direction(face.direction(u, v));
+                    assert_eq!(hit, face);
+                    assert!((hit_u - u).abs() < 1e-5 && (hit_v - v).abs() < 1e-5, "{face:?} ({u}, {v})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn equirectangular_top_row_is_up() {
+        let mut panorama = RenderTargetRgba32F::new(16, 8);
+        panorama.clear_image(Float4::new(0.0, 0.0, 1.0, 1.0));
+        for x in 0..16 {
+            panorama.set_pixel(x, 0, Float4::new(1.0, 0.0, 0.0, 1.0));
+            panorama.set_pixel(x, 7, Float4::new(0.0, 1.0, 0.0, 1.0));
+        }
+        let cube_map = CubeMap::from_equirectangular(&panorama, 8);
+
+        let up = cube_map.sample(Float3::new(0.0, 1.0, 0.0));
+        let down = cube_map.sample(Float3::new(0.0, -1.0, 0.0));
+        let horizon = cube_map.sample(Float3::new(1.0, 0.0, 0.0));
+        assert_eq!([up.x, up.y, up.z], [1.0, 0.0, 0.0]);
+        assert_eq!([down.x, down.y, down.z], [0.0, 1.0, 0.0]);
+        assert_eq!([horizon.x, horizon.y, horizon.z], [0.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,407 @@
+use crate::format::{PixelFormat, Rgba16F};
+use crate::framebuffer::Framebuffer;
+use crate::ibl::Ibl;
+use crate::image_view::{DepthBuffer, Image, RenderTargetR11G11B10F};
+use crate::light::{DirectionalLight, PointLight};
+use crate::math::{Float3, Float4, Matrix4};
+
+// Per pixel surface attributes written by the geometry pass. Shaders render into `framebuffer()`
+// with a `(albedo, normal, material, emissive)` output tuple:
+// - albedo: linear base color, alpha unused
+// - normal: world space normal, w is 1 where geometry was drawn
+// - material: metallic, roughness, occlusion
+// - emissive: linear emitted radiance, packed without alpha
+pub struct GBuffer {
+    pub albedo: Image<Rgba16F>,
+    pub normal: Image<Rgba16F>,
+    pub material: Image<Rgba16F>,
+    pub emissive: RenderTargetR11G11B10F,
+    pub depth: DepthBuffer,
+}
+
+pub type GBufferOutput = (Float4, Float4, Float4, Float4);
+
+type GBufferAttachments<'a> = (
+    &'a mut Image<Rgba16F>,
+    &'a mut Image<Rgba16F>,
+    &'a mut Image<Rgba16F>,
+    &'a mut RenderTargetR11G11B10F,
+);
+
+impl GBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            albedo: Image::new(width, height),
+            normal: Image::new(width, height),
+            material: Image::new(width, height),
+            emissive: Image::new(width, height),
+            depth: DepthBuffer::new(width, height),
+        }
+    }
+
+    pub fn clear(&mut self, depth: f32) {
+        self.albedo.clear_image(Float4::zero());
+        self.normal.clear_image(Float4::zero());
+        self.material.clear_image(Float4::zero());
+        self.emissive.clear_image(Float4::zero());
+        self.depth.clear_image(depth);
+    }
+
+    // Scales the material occlusion channel, e.g. by screen space ambient occlusion.
+    pub fn apply_occlusion(&mut self, occlusion: &Image<f32>) {
+        assert_eq!((self.material.width, self.material.height), (occlusion.width, occlusion.height));
+        for (material, &factor) in self.material.pixels.iter_mut().zip(&occlusion.pixels) {
+            let mut value = material.to_linear();
+            value.z *= factor;
+            *material = Rgba16F::from_linear(value);
+        }
+    }
+
+    pub fn framebuffer(&mut self) -> Framebuffer<'_, GBufferAttachments<'_>> {
+        Framebuffer::new((
+            &mut self.albedo,
+            &mut self.normal,
+            &mut self.material,
+            &mut self.emissive,
+        ))
+        .with_depth(&mut self.depth)
+    }
+}
+
+// Shades every covered G-buffer pixel once. Point lights are binned into screen tiles first so
+// each pixel only evaluates the lights whose range can reach it.
+pub struct DeferredRenderer {
+    pub tile_size: u32,
+    // Flat ambient light, replaced by image based lighting when `ibl` is set.
+    pub ambient: Float3,
+    pub ibl: Option<Ibl>,
+    tile_lights: Vec<Vec<u32>>,
+}
+
+impl Default for DeferredRenderer {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+struct Surface {
+    position: Float3,
+    normal: Float3,
+    albedo: Float3,
+    metallic: f32,
+    roughness: f32,
+}
+
+impl DeferredRenderer {
+    pub fn new(tile_size: u32) -> Self {
+        Self {
+            tile_size,
+            ambient: Float3::new(0.03, 0.03, 0.03),
+            ibl: None,
+            tile_lights: vec![],
+        }
+    }
+
+    // Lights culled into the tile covering pixel (x, y) during the last `light` call.
+    pub fn lights_in_tile(&self, x: u32, y: u32, width: u32) -> &[u32] {
+        let tiles_x = width.div_ceil(self.tile_size);
+        &self.tile_lights[((y / self.tile_size) * tiles_x + x / self.tile_size) as usize]
+    }
+
+    pub fn light<P: PixelFormat>(
+        &mut self,
+        gbuffer: &GBuffer,
+        view_proj: Matrix4,
+        camera_pos: Float3,
+        point_lights: &[PointLight],
+        dir_lights: &[DirectionalLight],
+        target: &mut Image<P>,
+    ) {
+        assert_eq!((gbuffer.depth.width, gbuffer.depth.height), (target.width, target.height));
+        let (width, height) = (target.width, target.height);
+        let Some(inverse_view_proj) = view_proj.inverse() else {
+            return;
+        };
+
+        self.cull_lights(gbuffer, view_proj, point_lights, width, height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+                // Background pixels keep whatever the target was cleared to.
+                if gbuffer.normal.pixels[index].to_linear().w == 0.0 {
+                    continue;
+                }
+                let lights = self
+                    .lights_in_tile(x, y, width)
+                    .iter()
+                    .map(|&light_index| &point_lights[light_index as usize]);
+                let position = world_position(gbuffer, x, y, inverse_view_proj);
+                let color = self.shade_pixel(gbuffer, index, position, camera_pos, lights, dir_lights);
+                target.set_linear(x, y, color);
+            }
+        }
+    }
+
+    fn shade_pixel<'a>(
+        &self,
+        gbuffer: &GBuffer,
+        index: usize,
+        position: Float3,
+        camera_pos: Float3,
+        point_lights: impl Iterator<Item = &'a PointLight>,
+        dir_lights: &[DirectionalLight],
+    ) -> Float4 {
+        let normal = gbuffer.normal.pixels[index].to_linear();
+        let albedo = gbuffer.albedo.pixels[index].to_linear();
+        let material = gbuffer.material.pixels[index].to_linear();
+        let emissive = gbuffer.emissive.pixels[index].to_linear();
+
+        let surface = Surface {
+            position,
+            normal: Float3::new(normal.x, normal.y, normal.z).normalize(),
+            albedo: Float3::new(albedo.x, albedo.y, albedo.z),
+            metallic: material.x,
+            roughness: material.y,
+        };
+        let view_dir = (camera_pos - surface.position).normalize();
+
+        let mut color = match &self.ibl {
+            Some(ibl) => ibl.ambient(
+                surface.normal,
+                view_dir,
+                surface.albedo,
+                surface.metallic,
+                surface.roughness,
+                material.z,
+            ),
+            None => material.z * surface.albedo * self.ambient,
+        };
+        for light in dir_lights {
+            let radiance = light.intensity * light.color;
+            color = color + shade(&surface, view_dir, (-1.0 * light.direction).normalize(), radiance);
+        }
+        for light in point_lights {
+            let to_light = light.pos - surface.position;
+            let distance = to_light.length();
+            if distance >= light.range || distance == 0.0 {
+                continue;
+            }
+            let radiance = light.intensity * attenuation(distance, light.range) * light.color;
+            color = color + shade(&surface, view_dir, (1.0 / distance) * to_light, radiance);
+        }
+
+        Float4::new(color.x + emissive.x, color.y + emissive.y, color.z + emissive.z, 1.0)
+    }
+
+    // Assigns each point light to every tile its bounding box overlaps on screen and in depth.
+    fn cull_lights(
+        &mut self,
+        gbuffer: &GBuffer,
+        view_proj: Matrix4,
+        point_lights: &[PointLight],
+        width: u32,
+        height: u32,
+    ) {
+        let tiles_x = width.div_ceil(self.tile_size);
+        let tiles_y = height.div_ceil(self.tile_size);
+        self.tile_lights.iter_mut().for_each(Vec::clear);
+        self.tile_lights.resize((tiles_x * tiles_y) as usize, vec![]);
+
+        // Depth range of the geometry in each tile, empty tiles get no lights at all.
+        let mut tile_depth = vec![(f32::MAX, f32::MIN); (tiles_x * tiles_y) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * gbuffer.depth.width + x) as usize;
+                if gbuffer.normal.pixels[index].to_linear().w == 0.0 {
+                    continue;
+                }
+                let depth = gbuffer.depth.pixels[index];
+                let tile = &mut tile_depth[((y / self.tile_size) * tiles_x + x / self.tile_size) as usize];
+                *tile = (tile.0.min(depth), tile.1.max(depth));
+            }
+        }
+
+        for (light_index, light) in point_lights.iter().enumerate() {
+            let Some((min, max)) = screen_bounds(light, view_proj, width, height) else {
+                continue;
+            };
+            let tile_of = |value: f32, size: u32| value.clamp(0.0, size as f32 - 1.0) as u32 / self.tile_size;
+            let x_range = tile_of(min.x, width)..=tile_of(max.x, width);
+            let y_range = tile_of(min.y, height)..=tile_of(max.y, height);
+            for tile_y in y_range {
+                for tile_x in x_range.clone() {
+                    let tile = (tile_y * tiles_x + tile_x) as usize;
+                    let (tile_min, tile_max) = tile_depth[tile];
+                    // Disjoint depth ranges mean no surface in the tile is inside the light's range.
+                    if min.z <= tile_max && max.z >= tile_min {
+                        self.tile_lights[tile].push(light_index as u32);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Reconstructs the world position from the pixel center and the stored depth.
+fn world_position(gbuffer: &GBuffer, x: u32, y: u32, inverse_view_proj: Matrix4) -> Float3 {
+    let (width, height) = (gbuffer.depth.width, gbuffer.depth.height);
+    let ndc = Float4::new(
+        (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+        1.0 - (y as f32 + 0.5) / height as f32 * 2.0,
+        gbuffer.depth.pixels[(y * width + x) as usize],
+        1.0,
+    );
+    let world = inverse_view_proj * ndc;
+    (1.0 / world.w) * Float3::new(world.x, world.y, world.z)
+}
+
+// Pixel space bounds of the light's bounding box, with z holding its depth range. Lights
+// straddling the camera plane cover the whole screen, lights entirely off screen return None.
+fn screen_bounds(light: &PointLight, view_proj: Matrix4, width: u32, height: u32) -> Option<(Float3, Float3)> {
+    let mut min = Float3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Float3::new(f32::MIN, f32::MIN, f32::MIN);
+    for corner in 0..8 {
+        let offset = Float3::new(
+            if corner & 1 == 0 { -light.range } else { light.range },
+            if corner & 2 == 0 { -light.range } else { light.range },
+            if corner & 4 == 0 { -light.range } else { light.range },
+        );
+        let clip = view_proj * (light.pos + offset).as_point();
+        if clip.w <= 0.0 {
+            return Some((
+                Float3::new(0.0, 0.0, f32::MIN),
+                Float3::new(width as f32, height as f32, f32::MAX),
+            ));
+        }
+        let screen = Float3::new(
+            (clip.x / clip.w * 0.5 + 0.5) * width as f32,
+            (0.5 - clip.y / clip.w * 0.5) * height as f32,
+            clip.z / clip.w,
+        );
+        min = Float3::new(min.x.min(screen.x), min.y.min(screen.y), min.z.min(screen.z));
+        max = Float3::new(max.x.max(screen.x), max.y.max(screen.y), max.z.max(screen.z));
+    }
+    let visible = max.x >= 0.0 && max.y >= 0.0 && min.x < width as f32 && min.y < height as f32;
+    visible.then_some((min, max))
+}
+
+// Inverse square falloff windowed to reach zero at the light's range, which makes culling exact.
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = (1.0 - ratio * ratio * ratio * ratio).clamp(0.0, 1.0);
+    window * window / (distance * distance).max(1e-4)
+}
+
+// Lambert diffuse plus a Blinn-Phong lobe whose sharpness follows roughness.
+fn shade(surface: &Surface, view_dir: Float3, light_dir: Float3, radiance: Float3) -> Float3 {
+    let n_dot_l = surface.normal.dot(light_dir);
+    if n_dot_l <= 0.0 {
+        return Float3::zero();
+    }
+    let half = (view_dir + light_dir).normalize();
+    let shininess = 2.0 / (surface.roughness * surface.roughness).max(1e-3) - 2.0;
+    let specular = surface.normal.dot(half).max(0.0).powf(shininess) * (shininess + 8.0) / 8.0;
+
+    let f0 = (1.0 - surface.metallic) * Float3::new(0.04, 0.04, 0.04) + surface.metallic * surface.albedo;
+    let diffuse = (1.0 - surface.metallic) * surface.albedo;
+    n_dot_l * (diffuse + specular * f0) * radiance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 64;
+
+    // A surface facing the camera at depth 0.5, with an identity view projection world space
+    // matches NDC.
+    fn flat_gbuffer() -> GBuffer {
+        let mut gbuffer = GBuffer::new(SIZE, SIZE);
+        gbuffer.clear(1.0);
+        gbuffer.albedo.clear_image(Float4::new(0.8, 0.6, 0.4, 1.0));
+        gbuffer.normal.clear_image(Float4::new(0.0, 0.0, -1.0, 1.0));
+        gbuffer.material.clear_image(Float4::new(0.0, 0.5, 1.0, 0.0));
+        gbuffer.depth.clear_image(0.5);
+        gbuffer
+    }
+
+    fn point_light(x: f32, y: f32, z: f32, range: f32) -> PointLight {
+        PointLight {
+            pos: Float3::new(x, y, z),
+            intensity: 1.0,
+            color: Float3::new(1.0, 1.0, 1.0),
+            range,
+        }
+    }
+
+    #[test]
+    fn lights_are_culled_to_the_tiles_they_overlap() {
+        let gbuffer = flat_gbuffer();
+        let lights = [
+            // Centered in the top left tile.
+            point_light(-0.75, 0.75, 0.5, 0.1),
+            // Straddles the four center tiles.
+            point_light(0.0, 0.0, 0.5, 0.1),
+            // Over the bottom right tile but entirely behind its geometry.
+            point_light(0.75, -0.75, 0.9, 0.1),
+        ];
+        let mut renderer = DeferredRenderer::new(16);
+        renderer.cull_lights(&gbuffer, Matrix4::identity(), &lights, SIZE, SIZE);
+
+        for tile_y in 0..4 {
+            for tile_x in 0..4 {
+                let mut expected = vec![];
+                if (tile_x, tile_y) == (0, 0) {
+                    expected.push(0);
+                }
+                if (1..=2).contains(&tile_x) && (1..=2).contains(&tile_y) {
+                    expected.push(1);
+                }
+                assert_eq!(renderer.lights_in_tile(tile_x * 16, tile_y * 16, SIZE), expected.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn culled_lighting_matches_brute_force() {
+        let mut gbuffer = flat_gbuffer();
+        // A step in depth gives tiles different depth ranges.
+        for y in 0..SIZE {
+            for x in SIZE / 2..SIZE {
+                gbuffer.depth.pixels[(y * SIZE + x) as usize] = 0.8;
+            }
+        }
+        let lights = [
+            point_light(-0.5, 0.5, 0.4, 0.3),
+            point_light(0.1, 0.0, 0.35, 0.4),
+            point_light(0.5, -0.5, 0.7, 0.2),
+            point_light(-0.9, -0.9, 0.45, 0.15),
+        ];
+        let camera_pos = Float3::new(0.0, 0.0, -1.0);
+        let mut target = Image::<Float4>::new(SIZE, SIZE);
+        let mut renderer = DeferredRenderer::new(16);
+        renderer.light(&gbuffer, Matrix4::identity(), camera_pos, &lights, &[], &mut target);
+
+        let mut lit = 0;
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let position = world_position(&gbuffer, x, y, Matrix4::identity());
+                let index = (y * SIZE + x) as usize;
+                let shade = |lights: &[PointLight]| {
+                    let color =
+                        renderer.shade_pixel(&gbuffer, index, position, camera_pos, lights.iter(), &[]);
+                    [color.x, color.y, color.z]
+                };
+                let expected = shade(&lights);
+                let actual = target.pixels[index];
+                assert_eq!([actual.x, actual.y, actual.z], expected, "({x}, {y})");
+                if expected != shade(&[]) {
+                    lit += 1;
+                }
+            }
+        }
+        // Most of the screen is out of range of every light.
+        assert!(lit > 0 && lit < SIZE * SIZE / 2, "{lit} pixels lit");
+    }
+}
@@ -0,0 +1,225 @@
+use crate::math::{Color, Float4};
+
+// Storage formats for render targets, all converted to and from linear RGBA.
+pub trait PixelFormat: Copy + Default {
+    fn from_linear(value: Float4) -> Self;
+    fn to_linear(self) -> Float4;
+
+    fn from_color(color: Color) -> Self {
+        Self::from_linear(Float4::from(color))
+    }
+}
+
+impl PixelFormat for Color {
+    fn from_linear(value: Float4) -> Self {
+        Color::from(value)
+    }
+
+    fn to_linear(self) -> Float4 {
+        Float4::from(self)
+    }
+
+    fn from_color(color: Color) -> Self {
+        color
+    }
+}
+
+impl PixelFormat for Float4 {
+    fn from_linear(value: Float4) -> Self {
+        value
+    }
+
+    fn to_linear(self) -> Float4 {
+        self
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Rgba16F {
+    pub bits: [u16; 4],
+}
+
+impl PixelFormat for Rgba16F {
+    fn from_linear(value: Float4) -> Self {
+        Self {
+            bits: [
+                f32_to_f16(value.x),
+                f32_to_f16(value.y),
+                f32_to_f16(value.z),
+                f32_to_f16(value.w),
+            ],
+        }
+    }
+
+    fn to_linear(self) -> Float4 {
+        Float4::new(
+            f16_to_f32(self.bits[0]),
+            f16_to_f32(self.bits[1]),
+            f16_to_f32(self.bits[2]),
+            f16_to_f32(self.bits[3]),
+        )
+    }
+}
+
+// Packed unsigned floats without alpha: red in the low 11 bits, then 11 bits of green and 10 of
+// blue. Negative values clamp to zero, alpha reads back as one.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct R11G11B10F {
+    pub bits: u32,
+}
+
+impl PixelFormat for R11G11B10F {
+    fn from_linear(value: Float4) -> Self {
+        let r = f32_to_unsigned_float(value.x, 6);
+        let g = f32_to_unsigned_float(value.y, 6);
+        let b = f32_to_unsigned_float(value.z, 5);
+        Self {
+            bits: r | (g << 11) | (b << 22),
+        }
+    }
+
+    fn to_linear(self) -> Float4 {
+        Float4::new(
+            unsigned_float_to_f32(self.bits & 0x7ff, 6),
+            unsigned_float_to_f32((self.bits >> 11) & 0x7ff, 6),
+            unsigned_float_to_f32(self.bits >> 22, 5),
+            1.0,
+        )
+    }
+}
+
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // Too small for a normal half, shift the implicit one into a subnormal.
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + round) as u16;
+    }
+
+    // A carry out of the mantissa correctly bumps the exponent.
+    let round = ((mantissa >> 12) & 1) as u16;
+    (sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16) + round
+}
+
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+
+    match exponent {
+        0 => {
+            let value = mantissa as f32 / (1 << 24) as f32;
+            if sign != 0 { -value } else { value }
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 112) << 23) | (mantissa << 13)),
+    }
+}
+
+// Small floats share the half exponent, they only keep fewer mantissa bits and no sign.
+fn f32_to_unsigned_float(value: f32, mantissa_bits: u32) -> u32 {
+    // `max` would keep -0.0, whose sign bit would land in the exponent.
+    let half = if value > 0.0 { f32_to_f16(value) as u32 } else { 0 };
+    (half >> (10 - mantissa_bits)).min(0x1f << mantissa_bits)
+}
+
+fn unsigned_float_to_f32(bits: u32, mantissa_bits: u32) -> f32 {
+    f16_to_f32((bits << (10 - mantissa_bits)) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_finite_half_round_trips() {
+        for half in 0..=u16::MAX {
+            if half & 0x7c00 != 0x7c00 {
+                assert_eq!(f32_to_f16(f16_to_f32(half)), half, "{half:#06x}");
+            }
+        }
+    }
+
+    #[test]
+    fn zero_keeps_its_sign() {
+        assert_eq!(f32_to_f16(0.0), 0);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert!(f16_to_f32(0x8000).is_sign_negative());
+    }
+
+    #[test]
+    fn subnormals() {
+        let smallest = 2.0f32.powi(-24);
+        assert_eq!(f32_to_f16(smallest), 0x0001);
+        assert_eq!(f16_to_f32(0x0001), smallest);
+        assert_eq!(f16_to_f32(0x03ff), 1023.0 * smallest);
+        assert_eq!(f32_to_f16(-3.0 * smallest), 0x8003);
+        // Less than half the smallest subnormal flushes to zero.
+        assert_eq!(f32_to_f16(2.0f32.powi(-26)), 0);
+    }
+
+    #[test]
+    fn rounding_carries_into_the_exponent() {
+        assert_eq!(f32_to_f16(2.0 - 2.0f32.powi(-12)), 0x4000);
+        // The largest subnormal rounds up to the smallest normal.
+        assert_eq!(f32_to_f16(2.0f32.powi(-14) - 2.0f32.powi(-26)), 0x0400);
+    }
+
+    #[test]
+    fn overflow_becomes_infinity() {
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1e10), 0x7c00);
+        assert_eq!(f32_to_f16(-1e10), 0xfc00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn nan_stays_nan() {
+        let half = f32_to_f16(f32::NAN);
+        assert_eq!(half & 0x7c00, 0x7c00);
+        assert_ne!(half & 0x3ff, 0);
+        assert!(f16_to_f32(half).is_nan());
+    }
+
+    #[test]
+    fn r11g11b10f_packs_red_green_blue_from_the_low_bits() {
+        assert_eq!(R11G11B10F::from_linear(Float4::new(1.0, 0.0, 0.0, 0.0)).bits, 0x3c0);
+        assert_eq!(R11G11B10F::from_linear(Float4::new(0.0, 1.0, 0.0, 0.0)).bits, 0x3c0 << 11);
+        assert_eq!(R11G11B10F::from_linear(Float4::new(0.0, 0.0, 1.0, 0.0)).bits, 0x1e0 << 22);
+
+        let value = R11G11B10F::from_linear(Float4::new(0.5, 2.0, 96.0, 0.25)).to_linear();
+        assert_eq!([value.x, value.y, value.z, value.w], [0.5, 2.0, 96.0, 1.0]);
+    }
+
+    #[test]
+    fn r11g11b10f_clamps_negative_values_to_zero() {
+        assert_eq!(R11G11B10F::from_linear(Float4::new(-1.0, -0.001, -1e10, -1.0)).bits, 0);
+        assert_eq!(R11G11B10F::from_linear(Float4::new(-0.0, 0.0, -0.0, 0.0)).bits, 0);
+    }
+
+    #[test]
+    fn r11g11b10f_overflows_to_infinity() {
+        let value = R11G11B10F::from_linear(Float4::new(1e10, 70000.0, f32::INFINITY, 1.0)).to_linear();
+        assert_eq!([value.x, value.y, value.z], [f32::INFINITY; 3]);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::format::PixelFormat;
+use crate::image_view::{DepthBuffer, Image};
+use crate::math::{Color, Float4};
+
+pub trait Attachment {
+    fn size(&self) -> (u32, u32);
+
+    // Used by the wireframe fill mode, which bypasses the fragment shader.
+    fn write_color(&mut self, x: u32, y: u32, color: Color);
+}
+
+// A set of attachments a fragment shader with the matching `Output` type can write to. Tuples
+// of attachments take tuples of outputs, one value per attachment.
+pub trait Attachments<Output>: Attachment {
+    fn write(&mut self, x: u32, y: u32, output: Output);
+}
+
+impl<P: PixelFormat> Attachment for Image<P> {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn write_color(&mut self, x: u32, y: u32, color: Color) {
+        self.set_pixel(x, y, P::from_color(color));
+    }
+}
+
+impl Attachments<Color> for Image<Color> {
+    fn write(&mut self, x: u32, y: u32, output: Color) {
+        self.set_pixel(x, y, output);
+    }
+}
+
+// Shaders writing linear values can target any format, including RGBA8 which clamps.
+impl<P: PixelFormat> Attachments<Float4> for Image<P> {
+    fn write(&mut self, x: u32, y: u32, output: Float4) {
+        self.set_linear(x, y, output);
+    }
+}
+
+// ID buffers, the wireframe has no meaningful id to write.
+impl Attachment for Image<u32> {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn write_color(&mut self, _x: u32, _y: u32, _color: Color) {}
+}
+
+impl Attachments<u32> for Image<u32> {
+    fn write(&mut self, x: u32, y: u32, output: u32) {
+        self.set_pixel(x, y, output);
+    }
+}
+
+impl<A: Attachment + ?Sized> Attachment for &mut A {
+    fn size(&self) -> (u32, u32) {
+        (**self).size()
+    }
+
+    fn write_color(&mut self, x: u32, y: u32, color: Color) {
+        (**self).write_color(x, y, color);
+    }
+}
+
+impl<Output, A: Attachments<Output> + ?Sized> Attachments<Output> for &mut A {
+    fn write(&mut self, x: u32, y: u32, output: Output) {
+        (**self).write(x, y, output);
+    }
+}
+
+macro_rules! impl_attachments_for_tuple {
+    ($($attachment:ident $output:ident $index:tt),+) => {
+        impl<$($attachment: Attachment),+> Attachment for ($($attachment,)+) {
+            fn size(&self) -> (u32, u32) {
+                let mut size = (u32::MAX, u32::MAX);
+                $(
+                    let (width, height) = self.$index.size();
+                    size = (size.0.min(width), size.1.min(height));
+                )+
+                size
+            }
+
+            fn write_color(&mut self, x: u32, y: u32, color: Color) {
+                $(self.$index.write_color(x, y, color);)+
+            }
+        }
+
+        impl<$($attachment: Attachments<$output>, $output),+> Attachments<($($output,)+)>
+            for ($($attachment,)+)
+        {
+            fn write(&mut self, x: u32, y: u32, output: ($($output,)+)) {
+                $(self.$index.write(x, y, output.$index);)+
+            }
+        }
+    };
+}
+
+impl_attachments_for_tuple!(A0 O0 0);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1, A2 O2 2);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1, A2 O2 2, A3 O3 3);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1, A2 O2 2, A3 O3 3, A4 O4 4);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1, A2 O2 2, A3 O3 3, A4 O4 4, A5 O5 5);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1, A2 O2 2, A3 O3 3, A4 O4 4, A5 O5 5, A6 O6 6);
+impl_attachments_for_tuple!(A0 O0 0, A1 O1 1, A2 O2 2, A3 O3 3, A4 O4 4, A5 O5 5, A6 O6 6, A7 O7 7);
+
+// The attachments a draw renders into. Without a depth attachment every fragment passes the
+// depth test and nothing is written.
+pub struct Framebuffer<'a, C> {
+    pub colors: C,
+    pub depth: Option<&'a mut DepthBuffer>,
+}
+
+impl<'a, C: Attachment> Framebuffer<'a, C> {
+    pub fn new(colors: C) -> Self {
+        Self {
+            colors,
+            depth: None,
+        }
+    }
+
+    pub fn with_depth(mut self, depth: &'a mut DepthBuffer) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = self.colors.size();
+        match &self.depth {
+            Some(depth) => (width.min(depth.width), height.min(depth.height)),
+            None => (width, height),
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use crate::cube_map::CubeMap;
+use crate::math::{self, Float2, Float3, Float4};
+use std::f32::consts::PI;
+
+// Image based lighting precomputed from an environment cube map with the split sum
+// approximation: a diffuse irradiance map, a specular map prefiltered per roughness level and a
+// BRDF integration table. Building is done once at load, sampling is cheap enough per fragment.
+pub struct Ibl {
+    pub irradiance: CubeMap,
+    // Level i is prefiltered for roughness i / (levels - 1) and half the size of level i - 1.
+    pub specular: Vec<CubeMap>,
+    pub brdf: BrdfLut,
+}
+
+pub struct IblSettings {
+    pub irradiance_size: u32,
+    pub irradiance_samples: u32,
+    pub specular_size: u32,
+    pub specular_levels: u32,
+    pub specular_samples: u32,
+    pub brdf_size: u32,
+    pub brdf_samples: u32,
+}
+
+impl Default for IblSettings {
+    fn default() -> Self {
+        Self {
+            irradiance_size: 32,
+            irradiance_samples: 512,
+            specular_size: 128,
+            specular_levels: 6,
+            specular_samples: 64,
+            brdf_size: 32,
+            brdf_samples: 256,
+        }
+    }
+}
+
+impl IblSettings {
+    // Every map needs at least one texel and one sample, and sampling needs a specular level.
+    fn validate(&self) {
+        let fields = [
+            ("irradiance_size", self.irradiance_size),
+            ("irradiance_samples", self.irradiance_samples),
+            ("specular_size", self.specular_size),
+            ("specular_levels", self.specular_levels),
+            ("specular_samples", self.specular_samples),
+            ("brdf_size", self.brdf_size),
+            ("brdf_samples", self.brdf_samples),
+        ];
+        for (name, value) in fields {
+            assert!(value > 0, "IblSettings::{name} must be positive");
+        }
+    }
+}
+
+impl Ibl {
+    pub fn new(environment: &CubeMap) -> Self {
+        Self::with_settings(environment, &IblSettings::default())
+    }
+
+    pub fn with_settings(environment: &CubeMap, settings: &IblSettings) -> Self {
+        settings.validate();
+
+        // Sampling blurry lobes from lower resolution copies keeps the sample counts small.
+        let mut radiance = vec![environment.resample(settings.specular_size)];
+        while radiance.last().unwrap().size > 1 {
+            radiance.push(radiance.last().unwrap().downsample());
+        }
+
+        Self {
+            irradiance: convolve_irradiance(
+                &radiance,
+                settings.irradiance_size,
+                settings.irradiance_samples,
+            ),
+            specular: (0..settings.specular_levels)
+                .map(|level| {
+                    let roughness = level as f32 / (settings.specular_levels - 1).max(1) as f32;
+                    let size = (settings.specular_size >> level).max(1);
+                    prefilter_specular(&radiance, size, roughness, settings.specular_samples)
+                })
+                .collect(),
+            brdf: BrdfLut::new(settings.brdf_size, settings.brdf_samples),
+        }
+    }
+
+    // Cosine weighted average of the incoming radiance around `normal`, multiply by the diffuse
+    // albedo for the outgoing radiance.
+    pub fn sample_irradiance(&self, normal: Float3) -> Float3 {
+        xyz(self.irradiance.sample(normal))
+    }
+
+    // Prefiltered radiance along the reflection vector, blending the two nearest roughness levels.
+    pub fn sample_specular(&self, reflection: Float3, roughness: f32) -> Float3 {
+        let max_level = (self.specular.len() - 1) as f32;
+        let level = roughness.clamp(0.0, 1.0) * max_level;
+        let lower = level.floor() as usize;
+        let upper = (lower + 1).min(self.specular.len() - 1);
+        let t = level - lower as f32;
+        let a = xyz(self.specular[lower].sample(reflection));
+        let b = xyz(self.specular[upper].sample(reflection));
+        (1.0 - t) * a + t * b
+    }
+
+    // Split sum ambient term for a metallic roughness surface. `view_dir` points from the surface
+    // towards the eye, `occlusion` is the baked or screen space ambient occlusion.
+    pub fn ambient(
+        &self,
+        normal: Float3,
+        view_dir: Float3,
+        albedo: Float3,
+        metallic: f32,
+        roughness: f32,
+        occlusion: f32,
+    ) -> Float3 {
+        let n_dot_v = normal.dot(view_dir).max(1e-4);
+        let f0 = (1.0 - metallic) * Float3::new(0.04, 0.04, 0.04) + metallic * albedo;
+
+        // Schlick's Fresnel with the roughness adjustment for a whole lobe of directions.
+        let fresnel_weight = (1.0 - n_dot_v).powi(5);
+        let grazing = (1.0 - roughness).max(f0.x.max(f0.y).max(f0.z));
+        let fresnel = f0 + fresnel_weight * (Float3::new(grazing, grazing, grazing) - f0);
+        let diffuse_weight = (1.0 - metallic) * (Float3::one() - fresnel);
+
+        let diffuse = diffuse_weight * albedo * self.sample_irradiance(normal);
+
+        let reflection = math::reflect(-1.0 * view_dir, normal);
+        let brdf = self.brdf.sample(n_dot_v, roughness);
+        let specular_weight = brdf.x * f0 + Float3::new(brdf.y, brdf.y, brdf.y);
+        let specular = specular_weight * self.sample_specular(reflection, roughness);
+
+        occlusion * (diffuse + specular)
+    }
+}
+
+// Scale and bias applied to F0 by the specular BRDF integrated over the hemisphere, indexed by
+// n dot v along x and roughness along y.
+pub struct BrdfLut {
+    pub size: u32,
+    pub data: Vec<Float2>,
+}
+
+impl BrdfLut {
+    pub fn new(size: u32, samples: u32) -> Self {
+        let mut data = Vec::with_capacity((size * size) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let n_dot_v = ((x as f32 + 0.5) / size as f32).max(1e-4);
+                let roughness = (y as f32 + 0.5) / size as f32;
+                data.push(integrate_brdf(n_dot_v, roughness, samples));
+            }
+        }
+        Self { size, data }
+    }
+
+    pub fn sample(&self, n_dot_v: f32, roughness: f32) -> Float2 {
+        let max = (self.size - 1) as f32;
+        let x = (n_dot_v * self.size as f32 - 0.5).clamp(0.0, max);
+        let y = (roughness * self.size as f32 - 0.5).clamp(0.0, max);
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+        let at = |x: u32, y: u32| self.data[(y * self.size + x) as usize];
+        let top = (1.0 - tx) * at(x0, y0) + tx * at(x1, y0);
+        let bottom = (1.0 - tx) * at(x0, y1) + tx * at(x1, y1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
+
+fn xyz(value: Float4) -> Float3 {
+    Float3::new(value.x, value.y, value.z)
+}
+
+// Low discrepancy points in the unit square.
+fn hammersley(i: u32, count: u32) -> (f32, f32) {
+    (i as f32 / count as f32, i.reverse_bits() as f32 * (1.0 / 4_294_967_296.0))
+}
+
+// Builds a tangent frame around `normal` for a direction given in its local space.
+fn to_world(local: Float3, normal: Float3) -> Float3 {
+    let up = if normal.z.abs() < 0.999 {
+        Float3::new(0.0, 0.0, 1.0)
+    } else {
+        Float3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = up.cross(normal).normalize();
+    let bitangent = normal.cross(tangent);
+    local.x * tangent + local.y * bitangent + local.z * normal
+}
+
+fn importance_sample_ggx(xi: (f32, f32), normal: Float3, roughness: f32) -> Float3 {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.0;
+    let cos_theta = ((1.0 - xi.1) / (1.0 + (a * a - 1.0) * xi.1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    to_world(
+        Float3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+        normal,
+    )
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness.powi(4);
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d).max(1e-8)
+}
+
+// Smith's shadowing with the k = a / 2 remapping used for image based lighting.
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    let g1 = |n_dot_x: f32| n_dot_x / (n_dot_x * (1.0 - k) + k);
+    g1(n_dot_v) * g1(n_dot_l)
+}
+
+// Trilinear lookup in a chain of successively halved cube maps.
+fn sample_lod(radiance: &[CubeMap], direction: Float3, lod: f32) -> Float3 {
+    let lod = lod.clamp(0.0, (radiance.len() - 1) as f32);
+    let lower = lod.floor() as usize;
+    let upper = (lower + 1).min(radiance.len() - 1);
+    let t = lod - lower as f32;
+    (1.0 - t) * xyz(radiance[lower].sample(direction)) + t * xyz(radiance[upper].sample(direction))
+}
+
+fn convolve_irradiance(radiance: &[CubeMap], size: u32, samples: u32) -> CubeMap {
+    // Cosine weighted samples are spread over the hemisphere, read them from a matching mip.
+    let texel_solid_angle = 4.0 * PI / (6.0 * (radiance[0].size * radiance[0].size) as f32);
+    let sample_solid_angle = 2.0 * PI / samples as f32;
+    let lod = 0.5 * (sample_solid_angle / texel_solid_angle).log2() + 1.0;
+
+    CubeMap::from_fn(size, |normal| {
+        let mut sum = Float3::zero();
+        for i in 0..samples {
+            let (u, v) = hammersley(i, samples);
+            let phi = 2.0 * PI * u;
+            let cos_theta = (1.0 - v).sqrt();
+            let sin_theta = v.sqrt();
+            let direction = to_world(
+                Float3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta),
+                normal,
+            );
+            sum = sum + sample_lod(radiance, direction, lod);
+        }
+        let irradiance = (1.0 / samples as f32) * sum;
+        Float4::new(irradiance.x, irradiance.y, irradiance.z, 1.0)
+    })
+}
+
+// Assumes the view direction equals the normal, the usual simplification for a single lookup.
+fn prefilter_specular(radiance: &[CubeMap], size: u32, roughness: f32, samples: u32) -> CubeMap {
+    if roughness == 0.0 {
+        return radiance
+            .iter()
+            .find(|level| level.size <= size)
+            .map(|level| CubeMap::from_fn(size, |direction| level.sample(direction)))
+            .unwrap();
+    }
+
+    let texel_solid_angle = 4.0 * PI / (6.0 * (radiance[0].size * radiance[0].size) as f32);
+    CubeMap::from_fn(size, |normal| {
+        let mut sum = Float3::zero();
+        let mut weight = 0.0;
+        for i in 0..samples {
+            let half = importance_sample_ggx(hammersley(i, samples), normal, roughness);
+            let n_dot_h = normal.dot(half).max(0.0);
+            let light = 2.0 * n_dot_h * half - normal;
+            let n_dot_l = normal.dot(light);
+            if n_dot_l <= 0.0 {
+                continue;
+            }
+            // Read low probability directions from blurrier mips so few samples don't alias.
+            let pdf = distribution_ggx(n_dot_h, roughness) / 4.0;
+            let sample_solid_angle = 1.0 / (samples as f32 * pdf + 1e-4);
+            let lod = 0.5 * (sample_solid_angle / texel_solid_angle).log2() + 1.0;
+            sum = sum + n_dot_l * sample_lod(radiance, light, lod);
+            weight += n_dot_l;
+        }
+        let prefiltered = (1.0 / weight.max(1e-4)) * sum;
+        Float4::new(prefiltered.x, prefiltered.y, prefiltered.z, 1.0)
+    })
+}
+
+fn integrate_brdf(n_dot_v: f32, roughness: f32, samples: u32) -> Float2 {
+    let view = Float3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+    let normal = Float3::new(0.0, 0.0, 1.0);
+    let (mut scale, mut bias) = (0.0, 0.0);
+    for i in 0..samples {
+        let half = importance_sample_ggx(hammersley(i, samples), normal, roughness);
+        let v_dot_h = view.dot(half).max(0.0);
+        let light = 2.0 * v_dot_h * half - view;
+        let n_dot_l = light.z.max(0.0);
+        if n_dot_l <= 0.0 {
+            continue;
+        }
+        let n_dot_h = half.z.max(0.0);
+        let visibility =
+            geometry_smith(n_dot_v, n_dot_l, roughness) * v_dot_h / (n_dot_h * n_dot_v).max(1e-6);
+        let fresnel = (1.0 - v_dot_h).powi(5);
+        scale += (1.0 - fresnel) * visibility;
+        bias += fresnel * visibility;
+    }
+    Float2::new(scale / samples as f32, bias / samples as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_settings() -> IblSettings {
+        IblSettings {
+            irradiance_size: 2,
+            irradiance_samples: 4,
+            specular_size: 4,
+            specular_levels: 1,
+            specular_samples: 4,
+            brdf_size: 2,
+            brdf_samples: 4,
+        }
+    }
+
+    #[test]
+    fn a_single_specular_level_samples_for_any_roughness() {
+        let environment = CubeMap::from_fn(4, |_| Float4::new(0.5, 0.5, 0.5, 1.0));
+        let ibl = Ibl::with_settings(&environment, &tiny_settings());
+        for roughness in [0.0, 0.5, 1.0] {
+            let radiance = ibl.sample_specular(Float3::new(0.0, 1.0, 0.0), roughness);
+            assert!((radiance.x - 0.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "IblSettings::specular_levels must be positive")]
+    fn rejects_zero_specular_levels() {
+        let environment = CubeMap::from_fn(4, |_| Float4::zero());
+        let settings = IblSettings {
+            specular_levels: 0,
+            ..tiny_settings()
+        };
+        Ibl::with_settings(&environment, &settings);
+    }
+
+    #[test]
+    #[should_panic(expected = "IblSettings::specular_size must be positive")]
+    fn rejects_zero_specular_size() {
+        let environment = CubeMap::from_fn(4, |_| Float4::zero());
+        let settings = IblSettings {
+            specular_size: 0,
+            ..tiny_settings()
+        };
+        Ibl::with_settings(&environment, &settings);
+    }
+}
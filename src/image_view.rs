@@ -1,4 +1,5 @@
-use crate::math::{Color, Float2, Float4};
+use crate::format::{PixelFormat, R11G11B10F, Rgba16F};
+use crate::math::{self, Color, Float2, Float3, Float4};
 use std::path::Path;
 
 pub struct Image<T> {
@@ -9,6 +10,9 @@ pub struct Image<T> {
 
 pub type Texture = Image<Color>;
 pub type RenderTarget = Image<Color>;
+pub type RenderTargetRgba16F = Image<Rgba16F>;
+pub type RenderTargetRgba32F = Image<Float4>;
+pub type RenderTargetR11G11B10F = Image<R11G11B10F>;
 pub type DepthBuffer = Image<f32>;
 
 impl<T: Copy + Default> Image<T> {
@@ -29,10 +33,31 @@ impl<T: Copy + Default> Image<T> {
     }
 }
 
-impl RenderTarget {
+impl<P: PixelFormat> Image<P> {
     pub fn clear_image(&mut self, color: Float4) {
-        let u8_color = Color::from(color);
-        self.pixels.fill(u8_color);
+        self.pixels.fill(P::from_linear(color));
+    }
+
+    pub fn get_linear(&self, x: u32, y: u32) -> Float4 {
+        self.pixels[(y * self.width + x) as usize].to_linear()
+    }
+
+    pub fn set_linear(&mut self, x: u32, y: u32, value: Float4) {
+        self.pixels[(y * self.width + x) as usize] = P::from_linear(value);
+    }
+
+    // Converts between formats of the same size, e.g. an HDR target to the RGBA8 present target.
+    pub fn convert_into<Q: PixelFormat>(&self, target: &mut Image<Q>) {
+        assert_eq!((self.width, self.height), (target.width, target.height));
+        for (dst, src) in target.pixels.iter_mut().zip(&self.pixels) {
+            *dst = Q::from_linear(src.to_linear());
+        }
+    }
+
+    pub fn convert<Q: PixelFormat>(&self) -> Image<Q> {
+        let mut target = Image::new(self.width, self.height);
+        self.convert_into(&mut target);
+        target
     }
 }
 
@@ -46,28 +71,30 @@ impl Texture {
     pub fn from_file(path: &Path) -> Texture {
         let decoded_image = stb_image::image::load(path);
         if let stb_image::image::LoadResult::ImageU8(image) = decoded_image {
-            let mut texture = Texture::new(image.width as u32, image.height as u32);
-            for y in 0..image.height {
-                for x in 0..image.width {
-                    let index = (y * image.width + x) * 3;
-                    texture.set_pixel(
-                        x as u32,
-                        y as u32,
-                        Color::new(
-                            image.data[index],
-                            image.data[index + 1],
-                            image.data[index + 2],
-                            255
-                        ),
-                    );
-                }
-            }
-            texture
+            Texture::from_u8(&image)
         } else {
             panic!("Unsupported texture type");
         }
     }
 
+    fn from_u8(image: &stb_image::image::Image<u8>) -> Texture {
+        let mut texture = Texture::new(image.width as u32, image.height as u32);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let index = (y * image.width + x) * image.depth;
+                let c = &image.data[index..index + image.depth];
+                let color = match image.depth {
+                    1 => Color::new(c[0], c[0], c[0], 255),
+                    2 => Color::new(c[0], c[0], c[0], c[1]),
+                    3 => Color::new(c[0], c[1], c[2], 255),
+                    _ => Color::new(c[0], c[1], c[2], c[3]),
+                };
+                texture.set_pixel(x as u32, y as u32, color);
+            }
+        }
+        texture
+    }
+
     pub fn pixel_at_uv(&self, uv: Float2) -> Color
     {
         let u = uv.x.rem_euclid(1.0);
@@ -77,6 +104,53 @@ impl Texture {
         let id = y * self.width as usize + x;
         self.pixels[id]
     }
+
+    // Color textures (albedo, emissive) are stored in sRGB and decoded for linear shading.
+    pub fn sample_srgb(&self, uv: Float2) -> Float4
+    {
+        math::srgb_color_to_linear(self.pixel_at_uv(uv))
+    }
+
+    // Data textures (normals, metallic roughness, occlusion) are already linear.
+    pub fn sample_linear(&self, uv: Float2) -> Float4
+    {
+        Float4::from(self.pixel_at_uv(uv))
+    }
+
+    pub fn normal_at_uv(&self, uv: Float2) -> Float3
+    {
+        math::decode_normal(self.pixel_at_uv(uv))
+    }
+}
+
+impl RenderTargetRgba32F {
+    // Radiance .hdr files load as linear floats, 8 bit images are decoded from sRGB.
+    pub fn from_file(path: &Path) -> RenderTargetRgba32F {
+        match stb_image::image::load(path) {
+            stb_image::image::LoadResult::ImageF32(image) => {
+                let mut target = RenderTargetRgba32F::new(image.width as u32, image.height as u32);
+                for (i, pixel) in target.pixels.iter_mut().enumerate() {
+                    let c = &image.data[i * image.depth..(i + 1) * image.depth];
+                    *pixel = match image.depth {
+                        1 => Float4::new(c[0], c[0], c[0], 1.0),
+                        2 => Float4::new(c[0], c[0], c[0], c[1]),
+                        3 => Float4::new(c[0], c[1], c[2], 1.0),
+                        _ => Float4::new(c[0], c[1], c[2], c[3]),
+                    };
+                }
+                target
+            }
+            stb_image::image::LoadResult::ImageU8(image) => {
+                let texture = Texture::from_u8(&image);
+                let mut target = RenderTargetRgba32F::new(texture.width, texture.height);
+                for (dst, &src) in target.pixels.iter_mut().zip(&texture.pixels) {
+                    *dst = math::srgb_color_to_linear(src);
+                }
+                target
+            }
+            stb_image::image::LoadResult::Error(error) => panic!("Failed to load {}: {}", path.display(), error),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
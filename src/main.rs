@@ -1,20 +1,45 @@
-use crate::command::{Command, CullMode, FillMode, Shader};
-use crate::image_view::{DepthBuffer, DepthTest, RenderTarget, Texture};
+use crate::animation::{skin_matrix, Pose};
+use crate::camera::Camera;
+use crate::command::{Command, CullMode, FillMode, FragmentBuiltins, Shader};
+use crate::cube_map::{CubeMap, SkyboxShader, SkyboxUniforms};
+use crate::deferred::{DeferredRenderer, GBuffer, GBufferOutput};
+use crate::framebuffer::{Attachments, Framebuffer};
+use crate::ibl::Ibl;
+use crate::image_view::{DepthBuffer, DepthTest, RenderTarget, RenderTargetRgba16F, Texture};
 use crate::light::{DirectionalLight, PointLight};
-use crate::math::{Color, Interpolate};
+use crate::math::Interpolate;
 use crate::math::{Float2, Float3, Float4, Matrix4};
-use crate::meshes::{Cube, Mesh, Model};
+use crate::meshes::{AlphaMode, Cube, Mesh, Model};
+use crate::oit::{OitBuffer, OitMode, Translucent};
+use crate::post::{Bloom, ColorGrading, Fxaa, Lut3D, PostEffect, PostStack, Vignette};
+use crate::ssao::Ssao;
+use crate::tonemap::{TonemapOperator, Tonemapper};
 use crate::viewport::Viewport;
 use crate::window::Window;
 use interpolate_macro::Interpolate;
+use sdl3::keyboard::Scancode;
 use std::path::Path;
 use std::time::Instant;
 
+mod animation;
+mod camera;
 mod command;
+mod cube_map;
+mod deferred;
+mod format;
+mod framebuffer;
+mod ibl;
 mod image_view;
 mod light;
 mod math;
 mod meshes;
+mod obj;
+mod oit;
+mod ply;
+mod post;
+mod ssao;
+mod stl;
+mod tonemap;
 mod viewport;
 mod window;
 
@@ -31,33 +56,375 @@ macro_rules! profile {
     }};
 }
 
+#[derive(Default, Debug, Clone, Copy, Interpolate)]
+struct VertexOutput {
+    pub position: Float4,
+    pub world_pos: Float4,
+    pub normal: Float3,
+    pub uv: Float2,
+    pub color: Float4,
+}
+
+struct MeshUniforms<'a> {
+    pub model: Matrix4,
+    pub perspective: Matrix4,
+    pub normal_matrix: Matrix4,
+    pub albedo_texture_index: Option<usize>,
+    pub emissive_texture_index: Option<usize>,
+    pub metal_rough_texture_index: Option<usize>,
+    pub occlusion_texture_index: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub joint_matrices: &'a [Matrix4],
+    pub morph_weights: &'a [f32],
+}
+
+fn mesh_vertex(vertex_index: u32, mesh: &Mesh, uniforms: &MeshUniforms<'_>) -> (VertexOutput, Float4) {
+    let i = vertex_index as usize;
+    let (mut position, mut normal) = if mesh.morph_targets.is_empty() {
+        (mesh.positions[i].as_point(), mesh.normals[i].as_vector())
+    } else {
+        (
+            mesh.morphed_position(i, uniforms.morph_weights).as_point(),
+            mesh.morphed_normal(i, uniforms.morph_weights).as_vector(),
+        )
+    };
+    if !uniforms.joint_matrices.is_empty() && !mesh.joints.is_empty() {
+        let skin = skin_matrix(mesh.joints[i], mesh.weights[i], uniforms.joint_matrices);
+        position = skin * position;
+        normal = skin * normal;
+    }
+    let world_pos = uniforms.model * position;
+    let normal = uniforms.normal_matrix * normal;
+    let vertex = VertexOutput {
+        position: uniforms.perspective * world_pos,
+        world_pos,
+        normal: Float3::new(normal.x, normal.y, normal.z),
+        uv: mesh.uvs[i],
+        color: mesh.color(i),
+    };
+    (vertex, vertex.position)
+}
+
+struct MeshShader<'a> {
+    pub textures: &'a [Texture],
+    pub point_lights: &'a [PointLight],
+    pub dir_lights: &'a [DirectionalLight],
+}
+
+impl<'a> Shader for MeshShader<'a> {
+    type VertexInput = Mesh;
+    type Varyings = VertexOutput;
+    type Uniforms = MeshUniforms<'a>;
+    type Output = Float4;
+
+    const MAY_DISCARD: bool = true;
+
+    fn vertex(&self, vertex_index: u32, mesh: &Mesh, uniforms: &MeshUniforms<'a>) -> (VertexOutput, Float4) {
+        mesh_vertex(vertex_index, mesh, uniforms)
+    }
+
+    fn fragment(
+        &self,
+        vertex: &VertexOutput,
+        _builtins: &FragmentBuiltins,
+        uniforms: &MeshUniforms<'a>,
+    ) -> Option<Float4> {
+        let albedo = uniforms
+            .albedo_texture_index
+            .and_then(|idx| self.textures.get(idx))
+            .map(|tex| tex.sample_srgb(vertex.uv))
+            .unwrap_or(Float4::new(1.0, 1.0, 1.0, 1.0))
+            * vertex.color;
+
+        if let AlphaMode::Mask(cutoff) = uniforms.alpha_mode
+            && albedo.w < cutoff
+        {
+            return None;
+        }
+
+        let emissive = uniforms
+            .emissive_texture_index
+            .and_then(|idx| self.textures.get(idx))
+            .map(|tex| tex.sample_srgb(vertex.uv))
+            .unwrap_or(Float4::zero());
+
+        let mut l0 = Float3::zero();
+        for point_light in self.point_lights {
+            let distance = (point_light.pos
+                - Float3::new(vertex.world_pos.x, vertex.world_pos.y, vertex.world_pos.z))
+            .length();
+            let attenuation = 1.0 / (distance * distance);
+            let radiance = point_light.intensity * attenuation * point_light.color;
+            l0 = l0 + radiance;
+        }
+
+        // Shading stays linear and unclamped, the HDR target is tonemapped for display.
+        Some(Float4::new(
+            albedo.x + emissive.x + l0.x,
+            albedo.y + emissive.y + l0.y,
+            albedo.z + emissive.z + l0.z,
+            if uniforms.alpha_mode == AlphaMode::Blend { albedo.w } else { 1.0 },
+        ))
+    }
+}
+
+struct GBufferShader<'a> {
+    pub textures: &'a [Texture],
+}
+
+impl<'a> Shader for GBufferShader<'a> {
+    type VertexInput = Mesh;
+    type Varyings = VertexOutput;
+    type Uniforms = MeshUniforms<'a>;
+    type Output = GBufferOutput;
+
+    const MAY_DISCARD: bool = true;
+
+    fn vertex(&self, vertex_index: u32, mesh: &Mesh, uniforms: &MeshUniforms<'a>) -> (VertexOutput, Float4) {
+        mesh_vertex(vertex_index, mesh, uniforms)
+    }
+
+    fn fragment(
+        &self,
+        vertex: &VertexOutput,
+        builtins: &FragmentBuiltins,
+        uniforms: &MeshUniforms<'a>,
+    ) -> Option<GBufferOutput> {
+        let albedo = uniforms
+            .albedo_texture_index
+            .and_then(|idx| self.textures.get(idx))
+            .map(|tex| tex.sample_srgb(vertex.uv))
+            .unwrap_or(Float4::new(1.0, 1.0, 1.0, 1.0))
+            * vertex.color;
+
+        if let AlphaMode::Mask(cutoff) = uniforms.alpha_mode
+            && albedo.w < cutoff
+        {
+            return None;
+        }
+
+        let emissive = uniforms
+            .emissive_texture_index
+            .and_then(|idx| self.textures.get(idx))
+            .map(|tex| tex.sample_srgb(vertex.uv))
+            .unwrap_or(Float4::zero());
+        // glTF packs roughness in green and metallic in blue.
+        let metal_rough = uniforms
+            .metal_rough_texture_index
+            .and_then(|idx| self.textures.get(idx))
+            .map(|tex| tex.sample_linear(vertex.uv))
+            .unwrap_or(Float4::new(0.0, 1.0, 0.0, 0.0));
+        let occlusion = uniforms
+            .occlusion_texture_index
+            .and_then(|idx| self.textures.get(idx))
+            .map(|tex| tex.sample_linear(vertex.uv).x)
+            .unwrap_or(1.0);
+
+        let mut normal = vertex.normal.normalize();
+        if !builtins.front_facing {
+            normal = -1.0 * normal;
+        }
+
+        Some((
+            Float4::new(albedo.x, albedo.y, albedo.z, 1.0),
+            Float4::new(normal.x, normal.y, normal.z, 1.0),
+            Float4::new(metal_rough.z, metal_rough.y, occlusion, 0.0),
+            Float4::new(emissive.x, emissive.y, emissive.z, 0.0),
+        ))
+    }
+}
+
+// Forward shading evaluates every light for every fragment, overdraw included. The deferred path
+// writes a G-buffer first and lights each visible pixel once.
+#[derive(Clone, Copy, Debug)]
+enum RenderPath {
+    Forward,
+    Deferred,
+}
+
+struct Scene<'a> {
+    pub cube: &'a Cube,
+    pub cube_texture: &'a Texture,
+    pub helmet: &'a Model,
+    pub time: f32,
+    pub view_proj: Matrix4,
+}
+
+// The helmet's animated state, evaluated once per frame and shared by every pass drawing it.
+struct ScenePose {
+    pub pose: Pose,
+    pub world_transforms: Vec<Matrix4>,
+    // One joint palette per node, empty for nodes without a skin.
+    pub joint_matrices: Vec<Vec<Matrix4>>,
+}
+
+impl ScenePose {
+    fn new(model: &Model, time: f32) -> Self {
+        let mut pose = model.rest_pose();
+        if let Some(animation) = model.animations.first()
+            && animation.duration > 0.0
+        {
+            animation.apply(time % animation.duration, &mut pose);
+        }
+        let world_transforms = model.world_transforms(&pose.transforms);
+        let joint_matrices = model
+            .nodes
+            .iter()
+            .map(|node| {
+                node.skin
+                    .map(|skin| model.skins[skin].joint_matrices(&world_transforms))
+                    .unwrap_or_default()
+            })
+            .collect();
+        Self {
+            pose,
+            world_transforms,
+            joint_matrices,
+        }
+    }
+}
+
+fn draw_scene<'a, 'f, S, C>(
+    command: &mut Command<'a>,
+    framebuffer: &mut Framebuffer<C>,
+    scene: &Scene<'a>,
+    scene_pose: &'f ScenePose,
+    make_shader: impl Fn(&'a [Texture]) -> S,
+    translucent: bool,
+) where
+    S: Shader<VertexInput = Mesh, Uniforms = MeshUniforms<'f>>,
+    C: Attachments<S::Output>,
+{
+    let Scene { cube, helmet, time, view_proj, .. } = *scene;
+    // Each pass only draws the meshes whose alpha mode belongs to it.
+    let in_pass = |mesh: &Mesh| (mesh.alpha_mode == AlphaMode::Blend) == translucent;
+
+    if in_pass(&cube.mesh) {
+        command.set_positions(&cube.mesh.positions);
+        command.set_indices(&cube.mesh.indices);
+        let model = Matrix4::translate(Float3::new(-2.0, 0.0, 0.0))
+            * Matrix4::rotate_yz(time)
+            * Matrix4::rotate_xy(time);
+
+        let cube_shader = make_shader(std::slice::from_ref(scene.cube_texture));
+        let uniforms = MeshUniforms {
+            model,
+            perspective: view_proj,
+            normal_matrix: model.normal_matrix(),
+            albedo_texture_index: cube.mesh.albedo_texture_index,
+            emissive_texture_index: cube.mesh.emissive_texture_index,
+            metal_rough_texture_index: cube.mesh.metal_rough_texture_index,
+            occlusion_texture_index: cube.mesh.occlusion_texture_index,
+            alpha_mode: cube.mesh.alpha_mode,
+            joint_matrices: &[],
+            morph_weights: &[],
+        };
+
+        command.draw_indexed(
+            framebuffer,
+            &cube_shader,
+            &cube.mesh,
+            &uniforms,
+        );
+    }
+
+    let model = Matrix4::translate(Float3::new(2.0, 0.0, 0.0))
+        * Matrix4::rotate_yz(time)
+        * Matrix4::rotate_xy(time);
+
+    let helmet_shader = make_shader(&helmet.textures);
+
+    for (node_index, node) in helmet.nodes.iter().enumerate() {
+        let joint_matrices = &scene_pose.joint_matrices[node_index];
+        // Skinned meshes ignore their node transform, the joints carry the whole pose.
+        let node_model = if joint_matrices.is_empty() {
+            model * scene_pose.world_transforms[node_index]
+        } else {
+            model
+        };
+
+        for &mesh_index in &node.meshes {
+            let mesh = &helmet.meshes[mesh_index];
+            if !in_pass(mesh) {
+                continue;
+            }
+            let uniforms = MeshUniforms {
+                model: node_model,
+                perspective: view_proj,
+                normal_matrix: node_model.normal_matrix(),
+                albedo_texture_index: mesh.albedo_texture_index,
+                emissive_texture_index: mesh.emissive_texture_index,
+                metal_rough_texture_index: mesh.metal_rough_texture_index,
+                occlusion_texture_index: mesh.occlusion_texture_index,
+                alpha_mode: mesh.alpha_mode,
+                joint_matrices,
+                morph_weights: &scene_pose.pose.morph_weights[node_index],
+            };
+
+            command.set_fill_mode(FillMode::Solid);
+
+            command.set_positions(&mesh.positions);
+            command.set_indices(&mesh.indices);
+            command.draw_indexed(
+                framebuffer,
+                &helmet_shader,
+                mesh,
+                &uniforms,
+            );
+        }
+    }
+}
+
+// Stand-in environment when no panorama is available, a sky gradient over a dark ground.
+fn procedural_sky(direction: Float3) -> Float4 {
+    let horizon = Float3::new(0.8, 0.85, 0.9);
+    let color = if direction.y >= 0.0 {
+        let t = direction.y.sqrt();
+        (1.0 - t) * horizon + t * Float3::new(0.2, 0.4, 0.8)
+    } else {
+        let t = (-direction.y).powf(0.3);
+        (1.0 - t) * horizon + t * Float3::new(0.1, 0.09, 0.08)
+    };
+    Float4::new(color.x, color.y, color.z, 1.0)
+}
+
 fn main() {
     let mut window = Window::new(1280, 720);
     let mut render_target = RenderTarget::new(1280, 720);
+    let mut hdr_target = RenderTargetRgba16F::new(1280, 720);
+    let mut exposure_ev = 0.0;
+    let mut tonemapper = Tonemapper::new(TonemapOperator::Aces);
+    let mut post_stack = PostStack::new();
+    post_stack.push(PostEffect::Bloom(Bloom::default()));
+    post_stack.push(PostEffect::Fxaa(Fxaa::default()));
+    post_stack.push(PostEffect::Vignette(Vignette::default()));
+    let lut_path = Path::new("assets/grading_lut.png");
+    let color_grading = lut_path
+        .exists()
+        .then(|| ColorGrading::new(Lut3D::from_strip(&Texture::from_file(lut_path))));
 
     let texture = Texture::from_file(Path::new("assets/bojan.jpg"));
+    let environment_path = Path::new("assets/environment.hdr");
+    let environment = if environment_path.exists() {
+        CubeMap::load_equirectangular(environment_path, 256)
+    } else {
+        CubeMap::from_fn(64, procedural_sky)
+    };
+    let skybox = Cube::new();
     let mut depth_buffer = DepthBuffer::new(1280, 720);
+    let mut gbuffer = GBuffer::new(1280, 720);
+    let mut deferred = DeferredRenderer::default();
+    deferred.ibl = Some(Ibl::new(&environment));
+    let mut ssao = Ssao::default();
+    let mut render_path = RenderPath::Deferred;
+    let mut oit_mode = OitMode::WeightedBlended;
+    let mut oit_buffer = OitBuffer::new(1280, 720, oit_mode);
     let mut command = Command::new();
 
     let mut cube = Cube::new();
     cube.mesh.albedo_texture_index = Some(0);
     let helmet = Model::from_file(Path::new("assets/damaged_helmet.glb"));
-
-    pub struct MeshData<'a> {
-        pub mesh: &'a Mesh,
-        pub model: Matrix4,
-        pub perspective: Matrix4,
-        pub textures: &'a [Texture],
-        pub point_lights: &'a [PointLight],
-        pub dir_lights: &'a [DirectionalLight],
-    }
-
-    #[derive(Default, Debug, Clone, Copy, Interpolate)]
-    struct VertexOutput {
-        pub position: Float4,
-        pub world_pos: Float4,
-        pub uv: Float2,
-    }
+    let mut camera = Camera::orbit(Float3::zero(), 10.0);
 
     let point_lights: Vec<PointLight> = vec![
         PointLight {
@@ -81,52 +448,6 @@ fn main() {
         cast_shadow: false,
     }];
 
-    let shader = Shader {
-        vertex_shader: Box::new(
-            |vertex_index, mesh_data: &MeshData| -> (VertexOutput, Float4) {
-                let mut vertex = VertexOutput {
-                    position: Float4::zero(),
-                    world_pos: Float4::zero(),
-                    uv: Float2::zero(),
-                };
-                vertex.position = mesh_data.perspective
-                    * mesh_data.model
-                    * mesh_data.mesh.positions[vertex_index as usize].as_point();
-                vertex.world_pos =
-                    mesh_data.model * mesh_data.mesh.positions[vertex_index as usize].as_point();
-                vertex.uv = mesh_data.mesh.uvs[vertex_index as usize];
-                (vertex, vertex.position)
-            },
-        ),
-        fragment_shader: Box::new(|vertex: &VertexOutput, fragment_input: &MeshData| {
-            let albedo = fragment_input
-                .mesh
-                .albedo_texture_index
-                .and_then(|idx| fragment_input.textures.get(idx))
-                .map(|tex| tex.pixel_at_uv(vertex.uv))
-                .unwrap_or_else(|| Color::from(Float4::new(1.0, 1.0, 1.0, 1.0)));
-
-            let emissive = fragment_input
-                .mesh
-                .emissive_texture_index
-                .and_then(|idx| fragment_input.textures.get(idx))
-                .map(|tex| tex.pixel_at_uv(vertex.uv))
-                .unwrap_or_else(|| Color::from(Float4::new(0.0, 0.0, 0.0, 1.0)));
-
-            let mut l0 = Float3::zero();
-            for point_light in fragment_input.point_lights {
-                let distance = (point_light.pos
-                    - Float3::new(vertex.world_pos.x, vertex.world_pos.y, vertex.world_pos.z))
-                .length();
-                let attenuation = 1.0 / (distance * distance);
-                let radiance = point_light.intensity * attenuation * point_light.color;
-                l0 = l0 + radiance;
-            }
-
-            albedo + emissive + Color::from(Float4::new(l0.x, l0.y, l0.z, 1.0))
-        }),
-    };
-
     let mut last_time = Instant::now();
     let mut time: f32 = 0.0;
     while window.is_running() {
@@ -137,10 +458,51 @@ fn main() {
         time += dt;
         println!("FPS: {}", 1.0 / dt);
 
+        camera.update(&window, dt);
+
+        // T cycles the tonemap operator, minus and equals step the exposure by half a stop.
+        if window.is_key_pressed(Scancode::T) {
+            tonemapper.operator = tonemapper.operator.next();
+            println!("Tonemap operator: {:?}", tonemapper.operator);
+        }
+        let exposure_step = if window.is_key_pressed(Scancode::Equals) {
+            0.5
+        } else if window.is_key_pressed(Scancode::Minus) {
+            -0.5
+        } else {
+            0.0
+        };
+        if exposure_step != 0.0 {
+            exposure_ev += exposure_step;
+            tonemapper.set_exposure_ev(exposure_ev);
+            println!("Exposure: {exposure_ev} EV");
+        }
+        // R switches between forward and deferred shading.
+        if window.is_key_pressed(Scancode::R) {
+            render_path = match render_path {
+                RenderPath::Forward => RenderPath::Deferred,
+                RenderPath::Deferred => RenderPath::Forward,
+            };
+            println!("Render path: {render_path:?}");
+        }
+        // O switches between weighted blended and k-buffer transparency.
+        if window.is_key_pressed(Scancode::O) {
+            oit_mode = match oit_mode {
+                OitMode::WeightedBlended => OitMode::KBuffer(4),
+                OitMode::KBuffer(_) => OitMode::WeightedBlended,
+            };
+            let (width, height) = window.get_window_size();
+            oit_buffer = OitBuffer::new(width as u32, height as u32, oit_mode);
+            println!("Transparency: {oit_mode:?}");
+        }
+
         let (width, height) = window.get_window_size();
         if window.is_resized() {
             render_target = RenderTarget::new(width as u32, height as u32);
+            hdr_target = RenderTargetRgba16F::new(width as u32, height as u32);
             depth_buffer = DepthBuffer::new(width as u32, height as u32);
+            gbuffer = GBuffer::new(width as u32, height as u32);
+            oit_buffer = OitBuffer::new(width as u32, height as u32, oit_mode);
         }
 
         let viewport = Viewport {
@@ -156,72 +518,128 @@ fn main() {
         command.toggle_depth_write(true);
 
         profile!("Clear Time", {
-            command.clear_render_target(&mut render_target, Float4::new(0.0, 0.0, 0.0, 1.0));
+            command.clear_render_target(&mut hdr_target, Float4::new(0.0, 0.0, 0.0, 1.0));
             command.clear_depth_buffer(&mut depth_buffer, 1.0);
         });
 
         let aspect_ratio = width as f32 / height as f32;
 
-        let view_matrix = Matrix4::translate(Float3::new(0.0, 0.0, -10.0));
+        let view_proj = camera.view_projection_matrix(aspect_ratio);
 
-        let perspective =
-            Matrix4::perspective(0.01, 100.0, std::f32::consts::PI / 3.0, aspect_ratio);
+        let scene = Scene {
+            cube: &cube,
+            cube_texture: &texture,
+            helmet: &helmet,
+            time,
+            view_proj,
+        };
+        let scene_pose = ScenePose::new(&helmet, time);
 
         profile!("Mesh Render Time", {
-            command.set_positions(&cube.mesh.positions);
-            command.set_indices(&cube.mesh.indices);
-            let model = Matrix4::translate(Float3::new(-2.0, 0.0, 0.0))
-                * Matrix4::rotate_yz(time)
-                * Matrix4::rotate_xy(time);
-
-            let view_proj = perspective * view_matrix;
+            match render_path {
+                RenderPath::Forward => {
+                    let mut framebuffer = Framebuffer::new(&mut hdr_target).with_depth(&mut depth_buffer);
+                    draw_scene(
+                        &mut command,
+                        &mut framebuffer,
+                        &scene,
+                        &scene_pose,
+                        |textures| MeshShader {
+                            textures,
+                            point_lights: &point_lights,
+                            dir_lights: &dir_lights,
+                        },
+                        false,
+                    );
+                }
+                RenderPath::Deferred => {
+                    gbuffer.clear(1.0);
+                    draw_scene(
+                        &mut command,
+                        &mut gbuffer.framebuffer(),
+                        &scene,
+                        &scene_pose,
+                        |textures| GBufferShader { textures },
+                        false,
+                    );
+                    let occlusion = ssao.compute(&gbuffer.depth, Some(&gbuffer.normal), view_proj);
+                    gbuffer.apply_occlusion(occlusion);
+                    deferred.light(
+                        &gbuffer,
+                        view_proj,
+                        camera.position(),
+                        &point_lights,
+                        &dir_lights,
+                        &mut hdr_target,
+                    );
+                }
+            }
+        });
 
-            let mesh_data = MeshData {
-                mesh: &cube.mesh,
-                model,
-                perspective: view_proj,
-                textures: std::slice::from_ref(&texture),
-                point_lights: &point_lights,
-                dir_lights: &dir_lights,
+        // The sky only fills pixels the opaque pass left at the far plane.
+        profile!("Skybox Time", {
+            let depth = match render_path {
+                RenderPath::Forward => &mut depth_buffer,
+                RenderPath::Deferred => &mut gbuffer.depth,
             };
-
+            let mut framebuffer = Framebuffer::new(&mut hdr_target).with_depth(depth);
+            command.set_cull_mode(CullMode::None);
+            command.set_depth_test(DepthTest::LessOrEqual);
+            command.toggle_depth_write(false);
+            command.set_positions(&skybox.mesh.positions);
+            command.set_indices(&skybox.mesh.indices);
             command.draw_indexed(
-                &mut render_target,
-                &mut depth_buffer,
-                &shader,
-                &mesh_data,
-                &mesh_data,
+                &mut framebuffer,
+                &SkyboxShader {
+                    cube_map: &environment,
+                },
+                &skybox.mesh,
+                &SkyboxUniforms {
+                    view: camera.view_matrix(),
+                    projection: camera.projection_matrix(aspect_ratio),
+                },
             );
+            command.set_cull_mode(CullMode::BackFace);
+            command.set_depth_test(DepthTest::Less);
+            command.toggle_depth_write(true);
+        });
 
-            let model = Matrix4::translate(Float3::new(2.0, 0.0, 0.0))
-                * Matrix4::rotate_yz(time)
-                * Matrix4::rotate_xy(time);
-
-            for mesh in &helmet.meshes {
-                let mesh_data = MeshData {
-                    mesh: &mesh,
-                    model,
-                    perspective: view_proj,
-                    textures: &helmet.textures,
-                    point_lights: &point_lights,
-                    dir_lights: &dir_lights,
-                };
-
-                command.set_fill_mode(FillMode::Solid);
-
-                command.set_positions(&mesh.positions);
-                command.set_indices(&mesh.indices);
-                command.draw_indexed(
-                    &mut render_target,
-                    &mut depth_buffer,
-                    &shader,
-                    &mesh_data,
-                    &mesh_data,
-                );
-            }
+        // Translucent meshes test against the opaque depth without writing it, then composite.
+        profile!("Translucent Render Time", {
+            oit_buffer.clear();
+            let depth = match render_path {
+                RenderPath::Forward => &mut depth_buffer,
+                RenderPath::Deferred => &mut gbuffer.depth,
+            };
+            command.toggle_depth_write(false);
+            let mut framebuffer = Framebuffer::new(&mut oit_buffer).with_depth(depth);
+            draw_scene(
+                &mut command,
+                &mut framebuffer,
+                &scene,
+                &scene_pose,
+                |textures| {
+                    Translucent(MeshShader {
+                        textures,
+                        point_lights: &point_lights,
+                        dir_lights: &dir_lights,
+                    })
+                },
+                true,
+            );
+            command.toggle_depth_write(true);
+            oit_buffer.resolve(&mut hdr_target);
+        });
+
+        profile!("Post Time", {
+            post_stack.run(&hdr_target);
         });
 
         profile!("Present Time", {
+            tonemapper.apply(post_stack.output(), &mut render_target);
+            if let Some(color_grading) = &color_grading {
+                color_grading.apply(&mut render_target);
+            }
             window.present(&render_target);
         });
     }
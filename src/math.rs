@@ -6,6 +6,28 @@ pub trait Number: Copy + Zero + One {}
 
 pub trait Interpolate: Sized {
     fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self;
+
+    // The rasterizer passes both perspective-correct and screen-linear barycentrics so derived
+    // impls can honour `#[interpolate(noperspective)]` fields.
+    fn interp_weights(perspective: [f32; 3], _screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        Self::interp(perspective[0], perspective[1], perspective[2], a, b, c)
+    }
+}
+
+impl Interpolate for f32 {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        l0 * a + l1 * b + l2 * c
+    }
+}
+
+impl<T: Interpolate, const N: usize> Interpolate for [T; N] {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        std::array::from_fn(|i| T::interp(l0, l1, l2, &a[i], &b[i], &c[i]))
+    }
+
+    fn interp_weights(perspective: [f32; 3], screen: [f32; 3], a: &Self, b: &Self, c: &Self) -> Self {
+        std::array::from_fn(|i| T::interp_weights(perspective, screen, &a[i], &b[i], &c[i]))
+    }
 }
 
 impl Number for f32 {}
@@ -161,6 +183,14 @@ impl Float3
     {
         self.length_squared().sqrt()
     }
+
+    pub fn cross(self, rhs: Float3) -> Self {
+        Self {
+            x: self.y * rhs.z - self.z * rhs.y,
+            y: self.z * rhs.x - self.x * rhs.z,
+            z: self.x * rhs.y - self.y * rhs.x,
+        }
+    }
 }
 
 impl Mul<Float3> for f32 {
@@ -346,6 +376,12 @@ impl Color
     }
 }
 
+impl Interpolate for Color {
+    fn interp(l0: f32, l1: f32, l2: f32, a: &Self, b: &Self, c: &Self) -> Self {
+        Color::from(Float4::interp(l0, l1, l2, &Float4::from(*a), &Float4::from(*b), &Float4::from(*c)))
+    }
+}
+
 impl From<Float4> for Color {
     fn from(c: Float4) -> Self {
         Self {
@@ -385,7 +421,7 @@ impl Add for Color {
     }
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Debug)]
 pub struct Matrix4 {
     pub data: [f32; 16],
 }
@@ -473,6 +509,160 @@ impl Matrix4 {
             ],
         }
     }
+
+    pub fn perspective_infinite(near: f32, fov_y: f32, aspect_ratio: f32) -> Self {
+        let top = near * (fov_y / 2.0).tan();
+        let right = top * aspect_ratio;
+        Self {
+            data: [
+                near / right, 0.0, 0.0, 0.0,
+                0.0, near / top, 0.0, 0.0,
+                0.0, 0.0, -1.0, -near,
+                0.0, 0.0, -1.0, 0.0,
+            ],
+        }
+    }
+
+    // Maps the near plane to depth 1 and the far plane to 0, use with DepthTest::Greater.
+    pub fn perspective_reverse_z(near: f32, far: f32, fov_y: f32, aspect_ratio: f32) -> Self {
+        let top = near * (fov_y / 2.0).tan();
+        let right = top * aspect_ratio;
+        Self {
+            data: [
+                near / right, 0.0, 0.0, 0.0,
+                0.0, near / top, 0.0, 0.0,
+                0.0, 0.0, near / (far - near), far * near / (far - near),
+                0.0, 0.0, -1.0, 0.0,
+            ],
+        }
+    }
+
+    pub fn perspective_infinite_reverse_z(near: f32, fov_y: f32, aspect_ratio: f32) -> Self {
+        let top = near * (fov_y / 2.0).tan();
+        let right = top * aspect_ratio;
+        Self {
+            data: [
+                near / right, 0.0, 0.0, 0.0,
+                0.0, near / top, 0.0, 0.0,
+                0.0, 0.0, 0.0, near,
+                0.0, 0.0, -1.0, 0.0,
+            ],
+        }
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        Self {
+            data: [
+                2.0 / (right - left), 0.0, 0.0, -(right + left) / (right - left),
+                0.0, 2.0 / (top - bottom), 0.0, -(top + bottom) / (top - bottom),
+                0.0, 0.0, -1.0 / (far - near), -near / (far - near),
+                0.0, 0.0, 0.0, 1.0,
+            ],
+        }
+    }
+
+    pub fn look_at(eye: Float3, target: Float3, up: Float3) -> Self {
+        let forward = (target - eye).normalize();
+        let side = forward.cross(up).normalize();
+        let up = side.cross(forward);
+        Self {
+            data: [
+                side.x, side.y, side.z, -side.dot(eye),
+                up.x, up.y, up.z, -up.dot(eye),
+                -forward.x, -forward.y, -forward.z, forward.dot(eye),
+                0.0, 0.0, 0.0, 1.0,
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut result = Matrix4::new();
+        for i in 0..4 {
+            for j in 0..4 {
+                result.data[4 * j + i] = self.data[4 * i + j];
+            }
+        }
+        result
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self.data;
+        let s0 = m[0] * m[5] - m[4] * m[1];
+        let s1 = m[0] * m[6] - m[4] * m[2];
+        let s2 = m[0] * m[7] - m[4] * m[3];
+        let s3 = m[1] * m[6] - m[5] * m[2];
+        let s4 = m[1] * m[7] - m[5] * m[3];
+        let s5 = m[2] * m[7] - m[6] * m[3];
+
+        let c5 = m[10] * m[15] - m[14] * m[11];
+        let c4 = m[9] * m[15] - m[13] * m[11];
+        let c3 = m[9] * m[14] - m[13] * m[10];
+        let c2 = m[8] * m[15] - m[12] * m[11];
+        let c1 = m[8] * m[14] - m[12] * m[10];
+        let c0 = m[8] * m[13] - m[12] * m[9];
+
+        s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.data;
+        let s0 = m[0] * m[5] - m[4] * m[1];
+        let s1 = m[0] * m[6] - m[4] * m[2];
+        let s2 = m[0] * m[7] - m[4] * m[3];
+        let s3 = m[1] * m[6] - m[5] * m[2];
+        let s4 = m[1] * m[7] - m[5] * m[3];
+        let s5 = m[2] * m[7] - m[6] * m[3];
+
+        let c5 = m[10] * m[15] - m[14] * m[11];
+        let c4 = m[9] * m[15] - m[13] * m[11];
+        let c3 = m[9] * m[14] - m[13] * m[10];
+        let c2 = m[8] * m[15] - m[12] * m[11];
+        let c1 = m[8] * m[14] - m[12] * m[10];
+        let c0 = m[8] * m[13] - m[12] * m[9];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det.abs() <= f32::MIN_POSITIVE {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let data = [
+            (m[5] * c5 - m[6] * c4 + m[7] * c3) * inv_det,
+            (-m[1] * c5 + m[2] * c4 - m[3] * c3) * inv_det,
+            (m[13] * s5 - m[14] * s4 + m[15] * s3) * inv_det,
+            (-m[9] * s5 + m[10] * s4 - m[11] * s3) * inv_det,
+            (-m[4] * c5 + m[6] * c2 - m[7] * c1) * inv_det,
+            (m[0] * c5 - m[2] * c2 + m[3] * c1) * inv_det,
+            (-m[12] * s5 + m[14] * s2 - m[15] * s1) * inv_det,
+            (m[8] * s5 - m[10] * s2 + m[11] * s1) * inv_det,
+            (m[4] * c4 - m[5] * c2 + m[7] * c0) * inv_det,
+            (-m[0] * c4 + m[1] * c2 - m[3] * c0) * inv_det,
+            (m[12] * s4 - m[13] * s2 + m[15] * s0) * inv_det,
+            (-m[8] * s4 + m[9] * s2 - m[11] * s0) * inv_det,
+            (-m[4] * c3 + m[5] * c1 - m[6] * c0) * inv_det,
+            (m[0] * c3 - m[1] * c1 + m[2] * c0) * inv_det,
+            (-m[12] * s3 + m[13] * s1 - m[14] * s0) * inv_det,
+            (m[8] * s3 - m[9] * s1 + m[10] * s0) * inv_det,
+        ];
+
+        Some(Self { data })
+    }
+
+    // Inverse transpose of the upper 3x3, for transforming normals with as_vector().
+    pub fn normal_matrix(&self) -> Self {
+        let mut linear = *self;
+        linear.data[3] = 0.0;
+        linear.data[7] = 0.0;
+        linear.data[11] = 0.0;
+        linear.data[12] = 0.0;
+        linear.data[13] = 0.0;
+        linear.data[14] = 0.0;
+        linear.data[15] = 1.0;
+        linear
+            .inverse()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear)
+    }
 }
 
 impl ops::Mul<Float4> for Matrix4 {
@@ -566,6 +756,237 @@ impl AddAssign for Color {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Quaternion {
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    pub const fn identity() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+
+    pub fn from_axis_angle(axis: Float3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Self::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    pub fn to_axis_angle(self) -> (Float3, f32) {
+        let q = self.normalize();
+        let q = if q.w < 0.0 { -q } else { q };
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let sin = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if sin <= 1e-6 {
+            (Float3::new(1.0, 0.0, 0.0), 0.0)
+        } else {
+            (Float3::new(q.x / sin, q.y / sin, q.z / sin), angle)
+        }
+    }
+
+    // Angles in radians around X, Y then Z, i.e. R = Rz * Ry * Rx.
+    pub fn from_euler(angles: Float3) -> Self {
+        let (sx, cx) = (angles.x * 0.5).sin_cos();
+        let (sy, cy) = (angles.y * 0.5).sin_cos();
+        let (sz, cz) = (angles.z * 0.5).sin_cos();
+        Self::new(
+            sx * cy * cz - cx * sy * sz,
+            cx * sy * cz + sx * cy * sz,
+            cx * cy * sz - sx * sy * cz,
+            cx * cy * cz + sx * sy * sz,
+        )
+    }
+
+    pub fn to_euler(self) -> Float3 {
+        let Quaternion { x, y, z, w } = self.normalize();
+        let sin_y = (-2.0 * (x * z - y * w)).clamp(-1.0, 1.0);
+        if sin_y.abs() > 0.99999 {
+            let angle_z = (-2.0 * (x * y - z * w)).atan2(1.0 - 2.0 * (x * x + z * z));
+            return Float3::new(0.0, sin_y.asin(), angle_z);
+        }
+        Float3::new(
+            (2.0 * (y * z + x * w)).atan2(1.0 - 2.0 * (x * x + y * y)),
+            sin_y.asin(),
+            (2.0 * (x * y + z * w)).atan2(1.0 - 2.0 * (y * y + z * z)),
+        )
+    }
+
+    pub fn dot(self, rhs: Quaternion) -> f32 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len <= f32::MIN_POSITIVE {
+            return Self::identity();
+        }
+        Self::new(self.x / len, self.y / len, self.z / len, self.w / len)
+    }
+
+    pub fn conjugate(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn inverse(self) -> Self {
+        let len_squared = self.dot(self);
+        let c = self.conjugate();
+        Self::new(c.x / len_squared, c.y / len_squared, c.z / len_squared, c.w / len_squared)
+    }
+
+    pub fn rotate(self, v: Float3) -> Float3 {
+        let u = Float3::new(self.x, self.y, self.z);
+        let t = 2.0 * u.cross(v);
+        v + self.w * t + u.cross(t)
+    }
+
+    pub fn nlerp(self, rhs: Quaternion, t: f32) -> Self {
+        let rhs = if self.dot(rhs) < 0.0 { -rhs } else { rhs };
+        Self::new(
+            self.x + (rhs.x - self.x) * t,
+            self.y + (rhs.y - self.y) * t,
+            self.z + (rhs.z - self.z) * t,
+            self.w + (rhs.w - self.w) * t,
+        )
+        .normalize()
+    }
+
+    pub fn slerp(self, rhs: Quaternion, t: f32) -> Self {
+        let mut cos = self.dot(rhs);
+        let rhs = if cos < 0.0 {
+            cos = -cos;
+            -rhs
+        } else {
+            rhs
+        };
+        if cos > 0.9995 {
+            return self.nlerp(rhs, t);
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        let a = ((1.0 - t) * angle).sin() / sin;
+        let b = (t * angle).sin() / sin;
+        Self::new(
+            a * self.x + b * rhs.x,
+            a * self.y + b * rhs.y,
+            a * self.z + b * rhs.z,
+            a * self.w + b * rhs.w,
+        )
+    }
+
+    pub fn to_matrix(self) -> Matrix4 {
+        let Quaternion { x, y, z, w } = self.normalize();
+        Matrix4 {
+            data: [
+                1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0,
+                2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0,
+                2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0,
+                0.0, 0.0, 0.0, 1.0,
+            ],
+        }
+    }
+
+    // Expects the upper 3x3 of the matrix to be a pure rotation.
+    pub fn from_matrix(m: &Matrix4) -> Self {
+        let d = &m.data;
+        let trace = d[0] + d[5] + d[10];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new((d[9] - d[6]) / s, (d[2] - d[8]) / s, (d[4] - d[1]) / s, 0.25 * s)
+        } else if d[0] > d[5] && d[0] > d[10] {
+            let s = (1.0 + d[0] - d[5] - d[10]).sqrt() * 2.0;
+            Self::new(0.25 * s, (d[1] + d[4]) / s, (d[2] + d[8]) / s, (d[9] - d[6]) / s)
+        } else if d[5] > d[10] {
+            let s = (1.0 + d[5] - d[0] - d[10]).sqrt() * 2.0;
+            Self::new((d[1] + d[4]) / s, 0.25 * s, (d[6] + d[9]) / s, (d[2] - d[8]) / s)
+        } else {
+            let s = (1.0 + d[10] - d[0] - d[5]).sqrt() * 2.0;
+            Self::new((d[2] + d[8]) / s, (d[6] + d[9]) / s, 0.25 * s, (d[4] - d[1]) / s)
+        };
+        q.normalize()
+    }
+}
+
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+impl ops::Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Quaternion {
+        Quaternion::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(q: Quaternion) -> Self {
+        q.to_matrix()
+    }
+}
+
+impl Matrix4 {
+    pub fn rotate(axis: Float3, angle: f32) -> Self {
+        Quaternion::from_axis_angle(axis, angle).to_matrix()
+    }
+
+    pub fn from_trs(translation: Float3, rotation: Quaternion, scale: Float3) -> Self {
+        Matrix4::translate(translation) * rotation.to_matrix() * Matrix4::scale(scale)
+    }
+
+    pub fn decompose(&self) -> (Float3, Quaternion, Float3) {
+        let d = &self.data;
+        let translation = Float3::new(d[3], d[7], d[11]);
+
+        let column_x = Float3::new(d[0], d[4], d[8]);
+        let column_y = Float3::new(d[1], d[5], d[9]);
+        let column_z = Float3::new(d[2], d[6], d[10]);
+        let mut scale = Float3::new(column_x.length(), column_y.length(), column_z.length());
+        if column_x.cross(column_y).dot(column_z) < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        let mut rotation = Matrix4::identity();
+        for (j, (column, s)) in [(column_x, scale.x), (column_y, scale.y), (column_z, scale.z)]
+            .into_iter()
+            .enumerate()
+        {
+            if s != 0.0 {
+                rotation.data[j] = column.x / s;
+                rotation.data[4 + j] = column.y / s;
+                rotation.data[8 + j] = column.z / s;
+            }
+        }
+
+        (translation, Quaternion::from_matrix(&rotation), scale)
+    }
+}
+
 pub fn perspective_divide(mut point: Float4) -> Float4
 {
     point.x = point.x / point.w;
@@ -573,3 +994,272 @@ pub fn perspective_divide(mut point: Float4) -> Float4
     point.z = point.z / point.w;
     point
 }
+
+pub fn decode_normal(encoded: Color) -> Float3
+{
+    let n = Float4::from(encoded);
+    Float3::new(n.x * 2.0 - 1.0, n.y * 2.0 - 1.0, n.z * 2.0 - 1.0).normalize()
+}
+
+// Tangent w stores the bitangent sign, following the glTF convention.
+pub fn perturb_normal(normal: Float3, tangent: Float4, tangent_space_normal: Float3) -> Float3
+{
+    let n = normal.normalize();
+    let t = Float3::new(tangent.x, tangent.y, tangent.z);
+    let t = (t - t.dot(n) * n).normalize();
+    let b = tangent.w * n.cross(t);
+    (tangent_space_normal.x * t + tangent_space_normal.y * b + tangent_space_normal.z * n).normalize()
+}
+
+// Mirrors `incident` about `normal`, which must be normalized.
+pub fn reflect(incident: Float3, normal: Float3) -> Float3
+{
+    incident - 2.0 * incident.dot(normal) * normal
+}
+
+pub fn srgb_to_linear(value: f32) -> f32
+{
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32
+{
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Alpha is always stored linearly.
+pub fn srgb_color_to_linear(color: Color) -> Float4
+{
+    static TABLE: std::sync::OnceLock<[f32; 256]> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f32 / 255.0)));
+    Float4::new(
+        table[color[0] as usize],
+        table[color[1] as usize],
+        table[color[2] as usize],
+        color[3] as f32 / 255.0,
+    )
+}
+
+// Rounds to the nearest 8-bit value so decoding and re-encoding a texture is lossless.
+pub fn linear_to_srgb_color(value: Float4) -> Color
+{
+    let encode = |v: f32| (linear_to_srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    Color::new(
+        encode(value.x),
+        encode(value.y),
+        encode(value.z),
+        (value.w.clamp(0.0, 1.0) * 255.0).round() as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn approx_identity(m: &Matrix4, epsilon: f32) -> bool {
+        let identity = Matrix4::identity();
+        m.data
+            .iter()
+            .zip(identity.data.iter())
+            .all(|(a, b)| (a - b).abs() <= epsilon)
+    }
+
+    fn transform_point(m: Matrix4, p: Float3) -> Float3 {
+        let r = m * p.as_point();
+        Float3::new(r.x / r.w, r.y / r.w, r.z / r.w)
+    }
+
+    fn any_vector() -> impl Strategy<Value = Float3> {
+        (-10.0f32..10.0, -10.0f32..10.0, -10.0f32..10.0).prop_map(|(x, y, z)| Float3::new(x, y, z))
+    }
+
+    fn any_scale() -> impl Strategy<Value = Float3> {
+        (0.1f32..5.0, 0.1f32..5.0, 0.1f32..5.0).prop_map(|(x, y, z)| Float3::new(x, y, z))
+    }
+
+    proptest! {
+        #[test]
+        fn general_matrix_times_inverse_is_identity(data in prop::array::uniform16(-10.0f32..10.0)) {
+            let m = Matrix4 { data };
+            prop_assume!(m.determinant().abs() > 1.0);
+            let inverse = m.inverse().unwrap();
+            prop_assert!(approx_identity(&(m * inverse), 1e-3), "{:?}", m * inverse);
+            prop_assert!(approx_identity(&(inverse * m), 1e-3), "{:?}", inverse * m);
+        }
+
+        #[test]
+        fn affine_matrix_times_inverse_is_identity(
+            translation in any_vector(),
+            scale in any_scale(),
+            angles in (-3.0f32..3.0, -3.0f32..3.0, -3.0f32..3.0),
+        ) {
+            let m = Matrix4::translate(translation)
+                * Matrix4::rotate_xy(angles.0)
+                * Matrix4::rotate_yz(angles.1)
+                * Matrix4::rotate_zx(angles.2)
+                * Matrix4::scale(scale);
+            let inverse = m.inverse().unwrap();
+            prop_assert!(approx_identity(&(m * inverse), 1e-4), "{:?}", m * inverse);
+        }
+
+        #[test]
+        fn perspective_times_inverse_is_identity(
+            near in 0.01f32..1.0,
+            far in 10.0f32..1000.0,
+            fov_y in 0.5f32..2.5,
+            aspect_ratio in 0.5f32..3.0,
+        ) {
+            let m = Matrix4::perspective(near, far, fov_y, aspect_ratio);
+            let inverse = m.inverse().unwrap();
+            prop_assert!(approx_identity(&(m * inverse), 1e-3), "{:?}", m * inverse);
+        }
+
+        #[test]
+        fn transpose_is_involution(data in prop::array::uniform16(-10.0f32..10.0)) {
+            let m = Matrix4 { data };
+            prop_assert_eq!(m.transpose().transpose().data, m.data);
+        }
+
+        #[test]
+        fn normal_matrix_keeps_normals_perpendicular(
+            scale in any_scale(),
+            angle in -3.0f32..3.0,
+            tangent in any_vector(),
+            normal in any_vector(),
+        ) {
+            prop_assume!(tangent.length() > 0.1 && normal.length() > 0.1);
+            let normal = normal - (normal.dot(tangent) / tangent.length_squared()) * tangent;
+            prop_assume!(normal.length() > 0.1);
+
+            let m = Matrix4::rotate_xy(angle) * Matrix4::scale(scale);
+            let t = m * tangent.as_vector();
+            let n = m.normal_matrix() * normal.as_vector();
+            prop_assert!(t.dot(n).abs() <= 1e-3 * t.dot(t).sqrt() * n.dot(n).sqrt());
+        }
+    }
+
+    #[test]
+    fn look_at_moves_eye_to_origin() {
+        let eye = Float3::new(1.0, 2.0, 3.0);
+        let target = Float3::new(-4.0, 0.5, -2.0);
+        let view = Matrix4::look_at(eye, target, Float3::new(0.0, 1.0, 0.0));
+
+        let origin = transform_point(view, eye);
+        assert!(origin.length() < 1e-5);
+
+        let forward = transform_point(view, target);
+        let distance = (target - eye).length();
+        assert!(forward.x.abs() < 1e-4 && forward.y.abs() < 1e-4);
+        assert!((forward.z + distance).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orthographic_maps_box_to_clip_volume() {
+        let m = Matrix4::orthographic(-2.0, 4.0, -1.0, 3.0, 0.5, 20.0);
+        let min = transform_point(m, Float3::new(-2.0, -1.0, -0.5));
+        let max = transform_point(m, Float3::new(4.0, 3.0, -20.0));
+        assert!((min.x + 1.0).abs() < 1e-6 && (min.y + 1.0).abs() < 1e-6 && min.z.abs() < 1e-6);
+        assert!((max.x - 1.0).abs() < 1e-6 && (max.y - 1.0).abs() < 1e-6 && (max.z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn perspective_variants_map_near_and_far() {
+        let (near, far, fov_y) = (0.1, 50.0, std::f32::consts::FRAC_PI_2);
+        let near_point = Float3::new(0.0, 0.0, -near);
+        let far_point = Float3::new(0.0, 0.0, -far);
+
+        let standard = Matrix4::perspective(near, far, fov_y, 1.0);
+        assert!(transform_point(standard, near_point).z.abs() < 1e-5);
+        assert!((transform_point(standard, far_point).z - 1.0).abs() < 1e-5);
+
+        let reverse = Matrix4::perspective_reverse_z(near, far, fov_y, 1.0);
+        assert!((transform_point(reverse, near_point).z - 1.0).abs() < 1e-5);
+        assert!(transform_point(reverse, far_point).z.abs() < 1e-5);
+
+        let infinite = Matrix4::perspective_infinite(near, fov_y, 1.0);
+        assert!(transform_point(infinite, near_point).z.abs() < 1e-5);
+        assert!(transform_point(infinite, Float3::new(0.0, 0.0, -1e6)).z < 1.0);
+
+        let infinite_reverse = Matrix4::perspective_infinite_reverse_z(near, fov_y, 1.0);
+        assert!((transform_point(infinite_reverse, near_point).z - 1.0).abs() < 1e-5);
+        assert!(transform_point(infinite_reverse, Float3::new(0.0, 0.0, -1e6)).z > 0.0);
+    }
+    fn approx_quaternion(a: Quaternion, b: Quaternion, epsilon: f32) -> bool {
+        a.dot(b).abs() >= 1.0 - epsilon
+    }
+
+    fn any_quaternion() -> impl Strategy<Value = Quaternion> {
+        (any_vector(), -3.0f32..3.0).prop_filter_map("degenerate axis", |(axis, angle)| {
+            (axis.length() > 0.1).then(|| Quaternion::from_axis_angle(axis, angle))
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn quaternion_matrix_round_trip(q in any_quaternion()) {
+            let back = Quaternion::from_matrix(&q.to_matrix());
+            prop_assert!(approx_quaternion(q, back, 1e-5), "{:?} {:?}", q, back);
+        }
+
+        #[test]
+        fn quaternion_rotation_matches_matrix(q in any_quaternion(), v in any_vector()) {
+            let by_quaternion = q.rotate(v);
+            let by_matrix = q.to_matrix() * v.as_vector();
+            let delta = by_quaternion - Float3::new(by_matrix.x, by_matrix.y, by_matrix.z);
+            prop_assert!(delta.length() < 1e-4);
+        }
+
+        #[test]
+        fn quaternion_euler_round_trip(x in -1.5f32..1.5, y in -1.5f32..1.5, z in -1.5f32..1.5) {
+            let q = Quaternion::from_euler(Float3::new(x, y, z));
+            let back = Quaternion::from_euler(q.to_euler());
+            prop_assert!(approx_quaternion(q, back, 1e-5), "{:?} {:?}", q, back);
+
+            let matrix = Matrix4::rotate(Float3::new(0.0, 0.0, 1.0), z)
+                * Matrix4::rotate(Float3::new(0.0, 1.0, 0.0), y)
+                * Matrix4::rotate(Float3::new(1.0, 0.0, 0.0), x);
+            prop_assert!(approx_quaternion(q, Quaternion::from_matrix(&matrix), 1e-5));
+        }
+
+        #[test]
+        fn trs_decompose_round_trip(t in any_vector(), r in any_quaternion(), s in any_scale()) {
+            let (translation, rotation, scale) = Matrix4::from_trs(t, r, s).decompose();
+            prop_assert!((translation - t).length() < 1e-4);
+            prop_assert!((scale - s).length() < 1e-4);
+            prop_assert!(approx_quaternion(rotation, r, 1e-5));
+        }
+    }
+
+    #[test]
+    fn slerp_interpolates_angle_linearly() {
+        let axis = Float3::new(0.0, 1.0, 0.0);
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(axis, 2.0);
+
+        assert!(approx_quaternion(a.slerp(b, 0.0), a, 1e-6));
+        assert!(approx_quaternion(a.slerp(b, 1.0), b, 1e-6));
+        let (_, angle) = a.slerp(b, 0.25).to_axis_angle();
+        assert!((angle - 0.5).abs() < 1e-4);
+        assert!(approx_quaternion(a.nlerp(b, 0.5), a.slerp(b, 0.5), 1e-6));
+    }
+
+    #[test]
+    fn srgb_decode_encode_is_lossless() {
+        for value in 0..=255u8 {
+            let color = Color::new(value, value, value, value);
+            let round_trip = linear_to_srgb_color(srgb_color_to_linear(color));
+            for channel in 0..4 {
+                assert_eq!(round_trip[channel], value, "channel {channel}");
+            }
+        }
+    }
+}
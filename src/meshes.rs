@@ -1,12 +1,29 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 use std::path::Path;
 use gltf::Document;
+use crate::animation::{Animation, Channel, ChannelOutputs, Interpolation, Pose, Skin, Transform};
 use crate::image_view::Texture;
-use crate::math::{Color, Float2, Float3};
+use crate::math::{Color, Float2, Float3, Float4, Matrix4, Quaternion};
 
 pub struct Model
 {
     pub meshes: Vec<Mesh>,
-    pub textures: Vec<Texture>
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<Node>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+}
+
+pub struct Node
+{
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub transform: Transform,
+    pub meshes: Vec<usize>,
+    pub skin: Option<usize>,
+    pub morph_weights: Vec<f32>,
 }
 
 impl Model
@@ -15,31 +32,61 @@ impl Model
     {
         let (document, buffers, images) = gltf::import(path).unwrap();
 
-        let meshes = Self::load_meshes(&document, &*buffers);
+        let (meshes, primitive_ranges) = Self::load_meshes(&document, &*buffers);
         let textures = Self::load_textures(&document, &*images);
+        let nodes = Self::load_nodes(&document, &primitive_ranges);
+        let skins = Self::load_skins(&document, &buffers);
+        let animations = Self::load_animations(&document, &buffers);
 
         Model{
             meshes,
             textures,
+            nodes,
+            skins,
+            animations,
+        }
+    }
+
+    pub fn rest_pose(&self) -> Pose
+    {
+        Pose {
+            transforms: self.nodes.iter().map(|node| node.transform).collect(),
+            morph_weights: self.nodes.iter().map(|node| node.morph_weights.clone()).collect(),
         }
     }
 
-    fn load_meshes(document: &Document, buffers: &[gltf::buffer::Data]) -> Vec<Mesh>
+    pub fn world_transforms(&self, transforms: &[Transform]) -> Vec<Matrix4>
+    {
+        let mut world = vec![Matrix4::identity(); self.nodes.len()];
+        let mut stack: Vec<(usize, Matrix4)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(index, _)| (index, Matrix4::identity()))
+            .collect();
+
+        while let Some((index, parent)) = stack.pop() {
+            world[index] = parent * transforms[index].matrix();
+            for &child in &self.nodes[index].children {
+                stack.push((child, world[index]));
+            }
+        }
+
+        world
+    }
+
+    fn load_meshes(document: &Document, buffers: &[gltf::buffer::Data]) -> (Vec<Mesh>, Vec<std::ops::Range<usize>>)
     {
         let mut meshes: Vec<Mesh> = vec![];
+        let mut primitive_ranges = vec![];
         for mesh in document.meshes()
         {
+            let first_primitive = meshes.len();
             for primitive in mesh.primitives() {
                 let mut mesh = Mesh{
-                    positions: vec![],
-                    indices: vec![],
-                    uvs: vec![],
-                    normals: vec![],
-                    albedo_texture_index: None,
-                    normal_texture_index: None,
-                    metal_rough_texture_index: None,
-                    occlusion_texture_index: None,
-                    emissive_texture_index: None,
+                    morph_weights: mesh.weights().map(<[f32]>::to_vec).unwrap_or_default(),
+                    ..Default::default()
                 };
 
                 let material = primitive.material();
@@ -65,6 +112,14 @@ impl Model
                     .emissive_texture()
                     .map(|info| info.texture().index());
 
+                mesh.alpha_mode = match material.alpha_mode() {
+                    gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                    gltf::material::AlphaMode::Mask => {
+                        AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+                    }
+                    gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+                };
+
                 let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
                 if let Some(indices_reader) = reader.read_indices() {
                     indices_reader.into_u32().for_each(|i| mesh.indices.push(i));
@@ -80,11 +135,190 @@ impl Model
                         .into_f32()
                         .for_each(|tc| mesh.uvs.push(Float2::new(tc[0], tc[1])));
                 }
+                if let Some(tex_coord_reader) = reader.read_tex_coords(1) {
+                    tex_coord_reader
+                        .into_f32()
+                        .for_each(|tc| mesh.uvs1.push(Float2::new(tc[0], tc[1])));
+                }
+                if let Some(colors_reader) = reader.read_colors(0) {
+                    colors_reader
+                        .into_rgba_f32()
+                        .for_each(|c| mesh.colors.push(Float4::new(c[0], c[1], c[2], c[3])));
+                }
+                for (semantic, accessor) in primitive.attributes() {
+                    if let gltf::Semantic::Extras(name) = semantic {
+                        match read_custom_attribute(&accessor, buffers) {
+                            Some(attribute) => {
+                                mesh.custom_attributes.insert(name, attribute);
+                            }
+                            None => eprintln!("Unsupported custom attribute: _{}", name),
+                        }
+                    }
+                }
+                if let Some(tangents_reader) = reader.read_tangents() {
+                    tangents_reader
+                        .for_each(|t| mesh.tangents.push(Float4::new(t[0], t[1], t[2], t[3])));
+                }
+
+                if let Some(joints_reader) = reader.read_joints(0) {
+                    joints_reader.into_u16().for_each(|j| mesh.joints.push(j));
+                }
+                if let Some(weights_reader) = reader.read_weights(0) {
+                    weights_reader
+                        .into_f32()
+                        .for_each(|w| mesh.weights.push(Float4::new(w[0], w[1], w[2], w[3])));
+                }
+
+                for (positions, normals, tangents) in reader.read_morph_targets() {
+                    mesh.morph_targets.push(MorphTarget {
+                        positions: positions
+                            .map(|p| p.map(|p| Float3::new(p[0], p[1], p[2])).collect())
+                            .unwrap_or_default(),
+                        normals: normals
+                            .map(|n| n.map(|n| Float3::new(n[0], n[1], n[2])).collect())
+                            .unwrap_or_default(),
+                        tangents: tangents
+                            .map(|t| t.map(|t| Float3::new(t[0], t[1], t[2])).collect())
+                            .unwrap_or_default(),
+                    });
+                }
+                mesh.morph_weights.resize(mesh.morph_targets.len(), 0.0);
+
+                // glTF asks for flat normals when a primitive has none.
+                if mesh.normals.is_empty() {
+                    mesh.unweld();
+                    mesh.generate_normals();
+                }
+                if mesh.tangents.is_empty() {
+                    mesh.generate_tangents();
+                }
                 meshes.push(mesh);
             }
+            primitive_ranges.push(first_primitive..meshes.len());
+        }
+
+        (meshes, primitive_ranges)
+    }
+
+    fn load_nodes(document: &Document, primitive_ranges: &[std::ops::Range<usize>]) -> Vec<Node>
+    {
+        let mut nodes: Vec<Node> = document
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                Node {
+                    name: node.name().map(str::to_owned),
+                    parent: None,
+                    children: node.children().map(|child| child.index()).collect(),
+                    transform: Transform {
+                        translation: Float3::new(translation[0], translation[1], translation[2]),
+                        rotation: Quaternion::new(rotation[0], rotation[1], rotation[2], rotation[3]),
+                        scale: Float3::new(scale[0], scale[1], scale[2]),
+                    },
+                    meshes: node
+                        .mesh()
+                        .map(|mesh| primitive_ranges[mesh.index()].clone().collect())
+                        .unwrap_or_default(),
+                    skin: node.skin().map(|skin| skin.index()),
+                    morph_weights: node
+                        .weights()
+                        .or_else(|| node.mesh().and_then(|mesh| mesh.weights()))
+                        .map(<[f32]>::to_vec)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        for index in 0..nodes.len() {
+            for child in nodes[index].children.clone() {
+                nodes[child].parent = Some(index);
+            }
         }
 
-        meshes
+        nodes
+    }
+
+    fn load_skins(document: &Document, buffers: &[gltf::buffer::Data]) -> Vec<Skin>
+    {
+        document
+            .skins()
+            .map(|skin| {
+                let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+                let inverse_bind_matrices = skin
+                    .reader(|buffer| Some(&buffers[buffer.index()]))
+                    .read_inverse_bind_matrices()
+                    .map(|matrices| matrices.map(matrix_from_columns).collect())
+                    .unwrap_or_else(|| vec![Matrix4::identity(); joints.len()]);
+
+                Skin {
+                    joints,
+                    inverse_bind_matrices,
+                    skeleton: skin.skeleton().map(|node| node.index()),
+                }
+            })
+            .collect()
+    }
+
+    fn load_animations(document: &Document, buffers: &[gltf::buffer::Data]) -> Vec<Animation>
+    {
+        use gltf::animation::util::ReadOutputs;
+
+        let mut animations: Vec<Animation> = vec![];
+        for animation in document.animations() {
+            let mut channels: Vec<Channel> = vec![];
+            for channel in animation.channels() {
+                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                let times: Vec<f32> = match reader.read_inputs() {
+                    Some(inputs) => inputs.collect(),
+                    None => continue,
+                };
+
+                let outputs = match reader.read_outputs() {
+                    Some(ReadOutputs::Translations(translations)) => ChannelOutputs::Translations(
+                        translations.map(|t| Float3::new(t[0], t[1], t[2])).collect(),
+                    ),
+                    Some(ReadOutputs::Rotations(rotations)) => ChannelOutputs::Rotations(
+                        rotations
+                            .into_f32()
+                            .map(|r| Quaternion::new(r[0], r[1], r[2], r[3]))
+                            .collect(),
+                    ),
+                    Some(ReadOutputs::Scales(scales)) => ChannelOutputs::Scales(
+                        scales.map(|s| Float3::new(s[0], s[1], s[2])).collect(),
+                    ),
+                    Some(ReadOutputs::MorphTargetWeights(weights)) => {
+                        ChannelOutputs::MorphWeights(weights.into_f32().collect())
+                    }
+                    None => continue,
+                };
+
+                let interpolation = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::Step => Interpolation::Step,
+                    gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                    gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                };
+
+                channels.push(Channel {
+                    node: channel.target().node().index(),
+                    interpolation,
+                    times,
+                    outputs,
+                });
+            }
+
+            let duration = channels
+                .iter()
+                .filter_map(|channel| channel.times.last().copied())
+                .fold(0.0, f32::max);
+
+            animations.push(Animation {
+                name: animation.name().map(str::to_owned),
+                channels,
+                duration,
+            });
+        }
+
+        animations
     }
 
     fn load_textures(document: &Document, images: &[gltf::image::Data]) -> Vec<Texture> {
@@ -135,16 +369,252 @@ impl Model
     }
 }
 
+// Only float accessors are read, normalized integer attributes are skipped.
+fn read_custom_attribute(accessor: &gltf::Accessor, buffers: &[gltf::buffer::Data]) -> Option<VertexAttribute>
+{
+    use gltf::accessor::{DataType, Dimensions, Iter};
+
+    if accessor.data_type() != DataType::F32 {
+        return None;
+    }
+    let get_buffer_data = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(|data| &data[..]);
+    let attribute = match accessor.dimensions() {
+        Dimensions::Scalar => VertexAttribute::Float(Iter::<f32>::new(accessor.clone(), get_buffer_data)?.collect()),
+        Dimensions::Vec2 => VertexAttribute::Float2(
+            Iter::<[f32; 2]>::new(accessor.clone(), get_buffer_data)?
+                .map(|v| Float2::new(v[0], v[1]))
+                .collect(),
+        ),
+        Dimensions::Vec3 => VertexAttribute::Float3(
+            Iter::<[f32; 3]>::new(accessor.clone(), get_buffer_data)?
+                .map(|v| Float3::new(v[0], v[1], v[2]))
+                .collect(),
+        ),
+        Dimensions::Vec4 => VertexAttribute::Float4(
+            Iter::<[f32; 4]>::new(accessor.clone(), get_buffer_data)?
+                .map(|v| Float4::new(v[0], v[1], v[2], v[3]))
+                .collect(),
+        ),
+        _ => return None,
+    };
+    Some(attribute)
+}
+
+// glTF matrices are stored column major.
+fn matrix_from_columns(columns: [[f32; 4]; 4]) -> Matrix4
+{
+    let mut matrix = Matrix4::new();
+    for (column, values) in columns.iter().enumerate() {
+        for (row, value) in values.iter().enumerate() {
+            matrix.data[4 * row + column] = *value;
+        }
+    }
+    matrix
+}
+
+pub enum VertexAttribute {
+    Float(Vec<f32>),
+    Float2(Vec<Float2>),
+    Float3(Vec<Float3>),
+    Float4(Vec<Float4>),
+}
+
+#[derive(Default)]
 pub struct Mesh {
     pub positions: Vec<Float3>,
     pub indices: Vec<u32>,
     pub uvs: Vec<Float2>,
     pub normals: Vec<Float3>,
+    pub tangents: Vec<Float4>,
+    pub colors: Vec<Float4>,
+    pub uvs1: Vec<Float2>,
+    pub custom_attributes: HashMap<String, VertexAttribute>,
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<Float4>,
+    pub morph_targets: Vec<MorphTarget>,
+    pub morph_weights: Vec<f32>,
     pub albedo_texture_index: Option<usize>,
     pub normal_texture_index: Option<usize>,
     pub metal_rough_texture_index: Option<usize>,
     pub occlusion_texture_index: Option<usize>,
     pub emissive_texture_index: Option<usize>,
+    pub alpha_mode: AlphaMode,
+}
+
+// Mask discards fragments whose base color alpha is below the cutoff.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    Mask(f32),
+    Blend,
+}
+
+// Per vertex deltas, attributes the target does not displace are left empty.
+pub struct MorphTarget {
+    pub positions: Vec<Float3>,
+    pub normals: Vec<Float3>,
+    pub tangents: Vec<Float3>,
+}
+
+impl Mesh {
+    pub fn color(&self, vertex: usize) -> Float4 {
+        self.colors
+            .get(vertex)
+            .copied()
+            .unwrap_or(Float4::new(1.0, 1.0, 1.0, 1.0))
+    }
+
+    pub fn morphed_position(&self, vertex: usize, weights: &[f32]) -> Float3 {
+        let mut position = self.positions[vertex];
+        for (target, &weight) in self.morph_targets.iter().zip(weights) {
+            if weight != 0.0 && !target.positions.is_empty() {
+                position = position + weight * target.positions[vertex];
+            }
+        }
+        position
+    }
+
+    pub fn morphed_normal(&self, vertex: usize, weights: &[f32]) -> Float3 {
+        let mut normal = self.normals[vertex];
+        for (target, &weight) in self.morph_targets.iter().zip(weights) {
+            if weight != 0.0 && !target.normals.is_empty() {
+                normal = normal + weight * target.normals[vertex];
+            }
+        }
+        normal.normalize()
+    }
+
+    fn triangles(&self) -> Vec<[usize; 3]> {
+        if self.indices.is_empty() {
+            (0..self.positions.len() / 3)
+                .map(|t| [3 * t, 3 * t + 1, 3 * t + 2])
+                .collect()
+        } else {
+            self.indices
+                .chunks_exact(3)
+                .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+                .collect()
+        }
+    }
+
+    // Gives every triangle corner its own vertex, so normals generated afterwards are flat.
+    pub fn unweld(&mut self) {
+        if self.indices.is_empty() {
+            return;
+        }
+        let indices = std::mem::take(&mut self.indices);
+        fn expand<T: Copy>(values: &mut Vec<T>, indices: &[u32]) {
+            if !values.is_empty() {
+                *values = indices.iter().map(|&i| values[i as usize]).collect();
+            }
+        }
+        expand(&mut self.positions, &indices);
+        expand(&mut self.uvs, &indices);
+        expand(&mut self.normals, &indices);
+        expand(&mut self.tangents, &indices);
+        expand(&mut self.colors, &indices);
+        expand(&mut self.uvs1, &indices);
+        expand(&mut self.joints, &indices);
+        expand(&mut self.weights, &indices);
+        for attribute in self.custom_attributes.values_mut() {
+            match attribute {
+                VertexAttribute::Float(values) => expand(values, &indices),
+                VertexAttribute::Float2(values) => expand(values, &indices),
+                VertexAttribute::Float3(values) => expand(values, &indices),
+                VertexAttribute::Float4(values) => expand(values, &indices),
+            }
+        }
+        for target in &mut self.morph_targets {
+            expand(&mut target.positions, &indices);
+            expand(&mut target.normals, &indices);
+            expand(&mut target.tangents, &indices);
+        }
+    }
+
+    // Area weighted smooth normals, counter-clockwise winding is front facing.
+    pub fn generate_normals(&mut self) {
+        let mut normals = vec![Float3::zero(); self.positions.len()];
+
+        for [i0, i1, i2] in self.triangles() {
+            let p0 = self.positions[i0];
+            let face_normal = (self.positions[i1] - p0).cross(self.positions[i2] - p0);
+            normals[i0] = normals[i0] + face_normal;
+            normals[i1] = normals[i1] + face_normal;
+            normals[i2] = normals[i2] + face_normal;
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|n| {
+                if n.length_squared() > 0.0 {
+                    n.normalize()
+                } else {
+                    Float3::new(0.0, 0.0, 1.0)
+                }
+            })
+            .collect();
+    }
+
+    // MikkTSpace style tangents: per corner tangent frames weighted by the corner angle,
+    // orthogonalized against the vertex normal, with the bitangent sign stored in w.
+    pub fn generate_tangents(&mut self) {
+        let vertex_count = self.positions.len();
+        if self.uvs.len() != vertex_count || self.normals.len() != vertex_count {
+            return;
+        }
+
+        let mut tangents = vec![Float3::zero(); vertex_count];
+        let mut bitangents = vec![Float3::zero(); vertex_count];
+
+        for triangle in self.triangles() {
+            let p = triangle.map(|i| self.positions[i]);
+            let uv = triangle.map(|i| self.uvs[i]);
+
+            let e1 = p[1] - p[0];
+            let e2 = p[2] - p[0];
+            let duv1 = uv[1] - uv[0];
+            let duv2 = uv[2] - uv[0];
+
+            let det = duv1.x * duv2.y - duv2.x * duv1.y;
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let r = 1.0 / det;
+            let tangent = r * (duv2.y * e1 - duv1.y * e2);
+            let bitangent = r * (duv1.x * e2 - duv2.x * e1);
+
+            for corner in 0..3 {
+                let a = p[(corner + 1) % 3] - p[corner];
+                let b = p[(corner + 2) % 3] - p[corner];
+                if a.length_squared() == 0.0 || b.length_squared() == 0.0 {
+                    continue;
+                }
+                let angle = a.normalize().dot(b.normalize()).clamp(-1.0, 1.0).acos();
+                let i = triangle[corner];
+                tangents[i] = tangents[i] + angle * tangent;
+                bitangents[i] = bitangents[i] + angle * bitangent;
+            }
+        }
+
+        self.tangents = (0..vertex_count)
+            .map(|i| {
+                let n = self.normals[i];
+                let mut t = tangents[i] - n.dot(tangents[i]) * n;
+                if t.length_squared() <= f32::EPSILON {
+                    let axis = if n.x.abs() < 0.9 {
+                        Float3::new(1.0, 0.0, 0.0)
+                    } else {
+                        Float3::new(0.0, 1.0, 0.0)
+                    };
+                    t = axis - n.dot(axis) * n;
+                }
+                let t = t.normalize();
+                let w = if n.cross(t).dot(bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+                Float4::new(t.x, t.y, t.z, w)
+            })
+            .collect();
+    }
 }
 
 pub struct Cube
@@ -231,17 +701,14 @@ impl Cube {
             Float2::new(1.0, 1.0),
         ];
 
-        let mesh = Mesh {
+        let mut mesh = Mesh {
             positions,
             indices,
             uvs,
-            normals: vec![],
-            albedo_texture_index: None,
-            normal_texture_index: None,
-            metal_rough_texture_index: None,
-            occlusion_texture_index: None,
-            emissive_texture_index: None,
+            ..Default::default()
         };
+        mesh.generate_normals();
+        mesh.generate_tangents();
 
         Self{
             mesh,
@@ -274,20 +741,567 @@ impl Square {
             Float2::new(1.0, 1.0),
         ];
 
-        let mesh = Mesh{
+        let mut mesh = Mesh{
             positions,
             indices,
             uvs,
-            normals: vec![],
-            albedo_texture_index: None,
-            normal_texture_index: None,
-            metal_rough_texture_index: None,
-            occlusion_texture_index: None,
-            emissive_texture_index: None,
+            ..Default::default()
         };
+        mesh.generate_normals();
+        mesh.generate_tangents();
 
         Self{
             mesh,
         }
     }
-}
\ No newline at end of file
+}
+
+// A point on the profile of a surface of revolution, in (radius, height) space.
+#[derive(Clone, Copy)]
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    normal_radius: f32,
+    normal_y: f32,
+    v: f32,
+}
+
+fn push_vertex(mesh: &mut Mesh, position: Float3, normal: Float3, uv: Float2, tangent: Float4) {
+    mesh.positions.push(position);
+    mesh.normals.push(normal);
+    mesh.uvs.push(uv);
+    mesh.tangents.push(tangent);
+}
+
+// Duplicates a vertex with a different u, for texture seams.
+fn copy_vertex(mesh: &mut Mesh, index: u32, u: f32) -> u32 {
+    let i = index as usize;
+    let uv = Float2::new(u, mesh.uvs[i].y);
+    push_vertex(mesh, mesh.positions[i], mesh.normals[i], uv, mesh.tangents[i]);
+    mesh.positions.len() as u32 - 1
+}
+
+fn sphere_tangent(normal: Float3) -> Float4 {
+    let tangent = Float3::new(normal.z, 0.0, -normal.x);
+    if tangent.length_squared() <= 1e-8 {
+        return Float4::new(1.0, 0.0, 0.0, -1.0);
+    }
+    let tangent = tangent.normalize();
+    Float4::new(tangent.x, tangent.y, tangent.z, -1.0)
+}
+
+// Sweeps the profile (ordered top to bottom) around the Y axis.
+fn revolve(mesh: &mut Mesh, profile: &[ProfilePoint], segments: u32) {
+    let base = mesh.positions.len() as u32;
+    let segments = segments.max(3);
+
+    for point in profile {
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            let position = Float3::new(point.radius * sin, point.y, point.radius * cos);
+            let normal = Float3::new(point.normal_radius * sin, point.normal_y, point.normal_radius * cos)
+                .normalize();
+            let tangent = Float4::new(cos, 0.0, -sin, -1.0);
+            push_vertex(mesh, position, normal, Float2::new(u, point.v), tangent);
+        }
+    }
+
+    let stride = segments + 1;
+    for ring in 0..profile.len().saturating_sub(1) {
+        for segment in 0..segments {
+            let a = base + ring as u32 * stride + segment;
+            let b = a + stride;
+            if profile[ring].radius != 0.0 {
+                mesh.indices.extend_from_slice(&[a, b, a + 1]);
+            }
+            if profile[ring + 1].radius != 0.0 {
+                mesh.indices.extend_from_slice(&[a + 1, b, b + 1]);
+            }
+        }
+    }
+}
+
+fn disk(mesh: &mut Mesh, y: f32, radius: f32, segments: u32, facing_up: bool) {
+    let segments = segments.max(3);
+    let normal = Float3::new(0.0, if facing_up { 1.0 } else { -1.0 }, 0.0);
+    let tangent = Float4::new(1.0, 0.0, 0.0, if facing_up { -1.0 } else { 1.0 });
+
+    let center = mesh.positions.len() as u32;
+    push_vertex(mesh, Float3::new(0.0, y, 0.0), normal, Float2::new(0.5, 0.5), tangent);
+    for segment in 0..=segments {
+        let (sin, cos) = (segment as f32 / segments as f32 * TAU).sin_cos();
+        push_vertex(
+            mesh,
+            Float3::new(radius * sin, y, radius * cos),
+            normal,
+            Float2::new(0.5 + 0.5 * sin, 0.5 + 0.5 * cos),
+            tangent,
+        );
+    }
+
+    for segment in 0..segments {
+        let a = center + 1 + segment;
+        if facing_up {
+            mesh.indices.extend_from_slice(&[center, a, a + 1]);
+        } else {
+            mesh.indices.extend_from_slice(&[center, a + 1, a]);
+        }
+    }
+}
+
+fn straight_profile(top: (f32, f32), bottom: (f32, f32), normal: (f32, f32), stacks: u32) -> Vec<ProfilePoint> {
+    let stacks = stacks.max(1);
+    (0..=stacks)
+        .map(|stack| {
+            let t = stack as f32 / stacks as f32;
+            ProfilePoint {
+                radius: top.0 + (bottom.0 - top.0) * t,
+                y: top.1 + (bottom.1 - top.1) * t,
+                normal_radius: normal.0,
+                normal_y: normal.1,
+                v: t,
+            }
+        })
+        .collect()
+}
+
+pub struct UvSphere {
+    pub mesh: Mesh
+}
+
+impl UvSphere {
+    pub fn new(radius: f32, segments: u32, rings: u32) -> Self
+    {
+        let rings = rings.max(2);
+        let profile: Vec<ProfilePoint> = (0..=rings)
+            .map(|ring| {
+                let v = ring as f32 / rings as f32;
+                let (sin, cos) = (v * PI).sin_cos();
+                ProfilePoint {
+                    // Pin the poles so the degenerate triangles there are dropped.
+                    radius: if ring == 0 || ring == rings { 0.0 } else { radius * sin },
+                    y: radius * cos,
+                    normal_radius: sin,
+                    normal_y: cos,
+                    v,
+                }
+            })
+            .collect();
+
+        let mut mesh = Mesh::default();
+        revolve(&mut mesh, &profile, segments);
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+pub struct IcoSphere {
+    pub mesh: Mesh
+}
+
+impl IcoSphere {
+    pub fn new(radius: f32, subdivisions: u32) -> Self
+    {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut directions: Vec<Float3> = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|&(x, y, z)| Float3::new(x, y, z).normalize())
+        .collect();
+
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| -> u32 {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    directions.push((0.5 * (directions[a as usize] + directions[b as usize])).normalize());
+                    directions.len() as u32 - 1
+                })
+            };
+
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let ab = midpoint(a, b);
+                    let bc = midpoint(b, c);
+                    let ca = midpoint(c, a);
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut mesh = Mesh::default();
+        for &normal in &directions {
+            let uv = Float2::new(
+                0.5 + normal.x.atan2(normal.z) / TAU,
+                normal.y.clamp(-1.0, 1.0).acos() / PI,
+            );
+            push_vertex(&mut mesh, radius * normal, normal, uv, sphere_tangent(normal));
+        }
+        let is_pole = |i: u32| {
+            let direction = directions[i as usize];
+            direction.x.abs() < 1e-6 && direction.z.abs() < 1e-6
+        };
+        let mut seam_copies: HashMap<u32, u32> = HashMap::new();
+        for [a, b, c] in triangles {
+            // Keep counter-clockwise winding as seen from outside.
+            let (pa, pb, pc) = (directions[a as usize], directions[b as usize], directions[c as usize]);
+            let outward = (pb - pa).cross(pc - pa).dot(pa + pb + pc) >= 0.0;
+            let mut corners = if outward { [a, b, c] } else { [a, c, b] };
+
+            // Triangles crossing the seam would interpolate u backwards over the whole texture, so
+            // their corners near u = 0 use a copy of the vertex at u + 1 instead.
+            let pole = corners.iter().position(|&i| is_pole(i));
+            let sides: Vec<usize> = (0..3).filter(|&corner| Some(corner) != pole).collect();
+            let us: Vec<f32> = sides.iter().map(|&corner| mesh.uvs[corners[corner] as usize].x).collect();
+            let span = us.iter().copied().fold(f32::MIN, f32::max) - us.iter().copied().fold(f32::MAX, f32::min);
+            if span > 0.5 {
+                for &corner in &sides {
+                    let index = corners[corner];
+                    let u = mesh.uvs[index as usize].x;
+                    if u < 0.5 {
+                        corners[corner] =
+                            *seam_copies.entry(index).or_insert_with(|| copy_vertex(&mut mesh, index, u + 1.0));
+                    }
+                }
+            }
+
+            // The u of a pole is undefined, so each triangle gets its own pole vertex centered above
+            // the opposite edge.
+            if let Some(pole) = pole {
+                let u = sides.iter().map(|&corner| mesh.uvs[corners[corner] as usize].x).sum::<f32>() / 2.0;
+                corners[pole] = copy_vertex(&mut mesh, corners[pole], u);
+            }
+
+            mesh.indices.extend_from_slice(&corners);
+        }
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+pub struct Cylinder {
+    pub mesh: Mesh
+}
+
+impl Cylinder {
+    pub fn new(radius: f32, height: f32, segments: u32, stacks: u32) -> Self
+    {
+        let half = height / 2.0;
+        let profile = straight_profile((radius, half), (radius, -half), (1.0, 0.0), stacks);
+
+        let mut mesh = Mesh::default();
+        revolve(&mut mesh, &profile, segments);
+        disk(&mut mesh, half, radius, segments, true);
+        disk(&mut mesh, -half, radius, segments, false);
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+pub struct Cone {
+    pub mesh: Mesh
+}
+
+impl Cone {
+    pub fn new(radius: f32, height: f32, segments: u32, stacks: u32) -> Self
+    {
+        let half = height / 2.0;
+        let slope = (height * height + radius * radius).sqrt();
+        let normal = (height / slope, radius / slope);
+        let profile = straight_profile((0.0, half), (radius, -half), normal, stacks);
+
+        let mut mesh = Mesh::default();
+        revolve(&mut mesh, &profile, segments);
+        disk(&mut mesh, -half, radius, segments, false);
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+pub struct Torus {
+    pub mesh: Mesh
+}
+
+impl Torus {
+    pub fn new(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> Self
+    {
+        let minor_segments = minor_segments.max(3);
+        // Starts at the top of the tube and runs down its outer side first.
+        let profile: Vec<ProfilePoint> = (0..=minor_segments)
+            .map(|segment| {
+                let v = segment as f32 / minor_segments as f32;
+                let (sin, cos) = (FRAC_PI_2 - v * TAU).sin_cos();
+                ProfilePoint {
+                    radius: major_radius + minor_radius * cos,
+                    y: minor_radius * sin,
+                    normal_radius: cos,
+                    normal_y: sin,
+                    v,
+                }
+            })
+            .collect();
+
+        let mut mesh = Mesh::default();
+        revolve(&mut mesh, &profile, major_segments);
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+pub struct Capsule {
+    pub mesh: Mesh
+}
+
+impl Capsule {
+    // `height` is the length of the cylindrical section between the two hemispheres.
+    pub fn new(radius: f32, height: f32, segments: u32, rings: u32) -> Self
+    {
+        let rings = rings.max(1);
+        let half = height / 2.0;
+        let total_length = PI * radius + height;
+
+        let mut profile: Vec<ProfilePoint> = vec![];
+        for (center, first_angle) in [(half, 0.0), (-half, FRAC_PI_2)] {
+            for ring in 0..=rings {
+                let angle = first_angle + ring as f32 / rings as f32 * FRAC_PI_2;
+                let (sin, cos) = angle.sin_cos();
+                let arc = if center > 0.0 { angle * radius } else { angle * radius + height };
+                let is_pole = (center > 0.0 && ring == 0) || (center < 0.0 && ring == rings);
+                profile.push(ProfilePoint {
+                    radius: if is_pole { 0.0 } else { radius * sin },
+                    y: center + radius * cos,
+                    normal_radius: sin,
+                    normal_y: cos,
+                    v: arc / total_length,
+                });
+            }
+        }
+
+        let mut mesh = Mesh::default();
+        revolve(&mut mesh, &profile, segments);
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+pub struct Plane {
+    pub mesh: Mesh
+}
+
+impl Plane {
+    // Lies in the XZ plane facing +Y.
+    pub fn new(width: f32, depth: f32, subdivisions_x: u32, subdivisions_z: u32) -> Self
+    {
+        let columns = subdivisions_x.max(1);
+        let rows = subdivisions_z.max(1);
+        let normal = Float3::new(0.0, 1.0, 0.0);
+        let tangent = Float4::new(1.0, 0.0, 0.0, -1.0);
+
+        let mut mesh = Mesh::default();
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let v = row as f32 / rows as f32;
+                let position = Float3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth);
+                push_vertex(&mut mesh, position, normal, Float2::new(u, v), tangent);
+            }
+        }
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = row * (columns + 1) + column;
+                let b = a + columns + 1;
+                mesh.indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+            }
+        }
+
+        Self{
+            mesh,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tangents must be unit length, orthogonal to the normal, and together with w * (n x t) follow
+    // the direction u and v increase in on every triangle.
+    fn assert_tangent_frames(mesh: &Mesh) {
+        for [a, b, c] in mesh.triangles() {
+            let (e1, e2) = (mesh.positions[b] - mesh.positions[a], mesh.positions[c] - mesh.positions[a]);
+            let (duv1, duv2) = (mesh.uvs[b] - mesh.uvs[a], mesh.uvs[c] - mesh.uvs[a]);
+            let r = 1.0 / (duv1.x * duv2.y - duv2.x * duv1.y);
+            let along_u = r * (duv2.y * e1 - duv1.y * e2);
+            let along_v = r * (duv1.x * e2 - duv2.x * e1);
+            for i in [a, b, c] {
+                let (n, t) = (mesh.normals[i], mesh.tangents[i]);
+                let tangent = Float3::new(t.x, t.y, t.z);
+                assert!((tangent.length() - 1.0).abs() < 1e-5);
+                assert!(n.dot(tangent).abs() < 1e-5);
+                assert!(t.w == 1.0 || t.w == -1.0);
+                assert!(tangent.dot(along_u) > 0.0);
+                assert!((t.w * n.cross(tangent)).dot(along_v) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_tangents_follow_the_uvs() {
+        assert_tangent_frames(&Cube::new().mesh);
+    }
+
+    #[test]
+    fn generated_plane_tangents_match_the_uvs() {
+        let mut mesh = Plane::new(2.0, 3.0, 2, 2).mesh;
+        let authored = mesh.tangents.clone();
+        mesh.generate_tangents();
+        assert_tangent_frames(&mesh);
+        for (generated, authored) in mesh.tangents.iter().zip(&authored) {
+            let difference = [generated.x - authored.x, generated.y - authored.y, generated.z - authored.z];
+            assert!(difference.iter().all(|d| d.abs() < 1e-5));
+            assert_eq!(generated.w, authored.w);
+        }
+    }
+
+    #[test]
+    fn unwelded_normals_are_flat() {
+        // Two triangles folded along a shared edge.
+        let mut mesh = Mesh {
+            positions: vec![
+                Float3::new(0.0, 0.0, 0.0),
+                Float3::new(1.0, 0.0, 0.0),
+                Float3::new(0.0, 1.0, 0.0),
+                Float3::new(1.0, 0.0, -1.0),
+            ],
+            indices: vec![0, 1, 2, 1, 3, 2],
+            ..Default::default()
+        };
+        mesh.unweld();
+        mesh.generate_normals();
+        assert_eq!(mesh.positions.len(), 6);
+        assert!(mesh.indices.is_empty());
+        for [a, b, c] in mesh.triangles() {
+            let (pa, pb, pc) = (mesh.positions[a], mesh.positions[b], mesh.positions[c]);
+            let face = (pb - pa).cross(pc - pa).normalize();
+            for i in [a, b, c] {
+                assert!((mesh.normals[i] - face).length() < 1e-5);
+            }
+        }
+    }
+
+    // A binary glTF with its JSON and buffer chunks padded to four bytes.
+    fn glb(json: &str, buffer: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut buffer = buffer.to_vec();
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+        let length = 12 + 8 + json.len() + 8 + buffer.len();
+        let mut data = b"glTF".to_vec();
+        data.extend(2u32.to_le_bytes());
+        data.extend((length as u32).to_le_bytes());
+        data.extend((json.len() as u32).to_le_bytes());
+        data.extend(b"JSON");
+        data.extend(json);
+        data.extend((buffer.len() as u32).to_le_bytes());
+        data.extend(b"BIN\0");
+        data.extend(buffer);
+        data
+    }
+
+    #[test]
+    fn custom_attributes_are_imported() {
+        let positions = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        let temperatures = [10.0f32, 20.0, 30.0, 40.0];
+        let indices = [0u16, 1, 2, 0, 2, 3];
+        let mut buffer = vec![];
+        positions.iter().flatten().for_each(|v| buffer.extend(v.to_le_bytes()));
+        temperatures.iter().for_each(|v| buffer.extend(v.to_le_bytes()));
+        indices.iter().for_each(|i| buffer.extend(i.to_le_bytes()));
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "buffers": [{"byteLength": 76}],
+            "bufferViews": [
+                {"buffer": 0, "byteOffset": 0, "byteLength": 48},
+                {"buffer": 0, "byteOffset": 48, "byteLength": 16},
+                {"buffer": 0, "byteOffset": 64, "byteLength": 12}
+            ],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                 "min": [0, 0, 0], "max": [1, 1, 0]},
+                {"bufferView": 1, "componentType": 5126, "count": 4, "type": "SCALAR"},
+                {"bufferView": 2, "componentType": 5123, "count": 6, "type": "SCALAR"}
+            ],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "_TEMPERATURE": 1}, "indices": 2}]}]
+        }"#;
+
+        let (document, buffers, _) = gltf::import_slice(glb(json, &buffer)).unwrap();
+        let (meshes, _) = Model::load_meshes(&document, &buffers);
+
+        // The primitive has no normals, so it is unwelded and the attribute follows the indices.
+        let Some(VertexAttribute::Float(values)) = meshes[0].custom_attributes.get("TEMPERATURE") else {
+            panic!("TEMPERATURE was not imported as a scalar attribute");
+        };
+        let expected: Vec<f32> = indices.iter().map(|&i| temperatures[i as usize]).collect();
+        assert_eq!(values, &expected);
+    }
+
+    fn ico_sphere() -> Mesh {
+        IcoSphere::new(2.0, 2).mesh
+    }
+
+    #[test]
+    fn ico_sphere_winds_counter_clockwise_from_outside() {
+        let mesh = ico_sphere();
+        for [a, b, c] in mesh.triangles() {
+            let (pa, pb, pc) = (mesh.positions[a], mesh.positions[b], mesh.positions[c]);
+            assert!((pb - pa).cross(pc - pa).dot(pa + pb + pc) > 0.0);
+        }
+    }
+
+    #[test]
+    fn ico_sphere_normals_face_outward() {
+        let mesh = ico_sphere();
+        for (&position, &normal) in mesh.positions.iter().zip(&mesh.normals) {
+            assert!((position.length() - 2.0).abs() < 1e-5);
+            assert!((normal - 0.5 * position).length() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn ico_sphere_triangles_do_not_wrap_around_the_seam() {
+        let mesh = ico_sphere();
+        let poles = mesh.positions.iter().filter(|p| p.x.abs() < 1e-6 && p.z.abs() < 1e-6).count();
+        assert!(poles > 0);
+        for [a, b, c] in mesh.triangles() {
+            let us = [mesh.uvs[a].x, mesh.uvs[b].x, mesh.uvs[c].x];
+            let span = us.iter().copied().fold(f32::MIN, f32::max) - us.iter().copied().fold(f32::MAX, f32::min);
+            assert!(span < 0.5, "u spans {us:?}");
+            assert!(us.iter().all(|u| (0.0..=1.5).contains(u)));
+        }
+    }
+}
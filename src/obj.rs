@@ -0,0 +1,420 @@
+use crate::animation::Transform;
+use crate::image_view::Texture;
+use crate::math::{Float2, Float3, Float4};
+use crate::meshes::{Mesh, Model, Node};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Default, Clone)]
+struct Material {
+    albedo_texture_index: Option<usize>,
+    normal_texture_index: Option<usize>,
+    emissive_texture_index: Option<usize>,
+}
+
+// Face corners are deduplicated on their (position, uv, normal) tuple.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct Attributes {
+    positions: Vec<Float3>,
+    colors: Vec<Float4>,
+    uvs: Vec<Float2>,
+    normals: Vec<Float3>,
+}
+
+struct MeshBuilder {
+    mesh: Mesh,
+    vertices: HashMap<Corner, u32>,
+    // Vertices whose corners had no `vn`, their normals are generated from the faces.
+    missing_normals: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn new(material: &Material) -> Self {
+        Self {
+            mesh: Mesh {
+                albedo_texture_index: material.albedo_texture_index,
+                normal_texture_index: material.normal_texture_index,
+                emissive_texture_index: material.emissive_texture_index,
+                ..Default::default()
+            },
+            vertices: HashMap::new(),
+            missing_normals: vec![],
+        }
+    }
+
+    fn vertex(&mut self, corner: Corner, attributes: &Attributes) -> u32 {
+        if let Some(&index) = self.vertices.get(&corner) {
+            return index;
+        }
+
+        let (position, uv, normal) = corner;
+        let index = self.mesh.positions.len() as u32;
+        self.mesh.positions.push(attributes.positions[position]);
+        if !attributes.colors.is_empty() {
+            // Vertices emitted before the first colored `v` line stay white.
+            self.mesh.colors.resize(index as usize, Float4::new(1.0, 1.0, 1.0, 1.0));
+            self.mesh.colors.push(attributes.colors[position]);
+        }
+        self.mesh
+            .uvs
+            .push(uv.map(|uv| attributes.uvs[uv]).unwrap_or_else(Float2::zero));
+        match normal {
+            Some(normal) => self.mesh.normals.push(attributes.normals[normal]),
+            None => {
+                self.mesh.normals.push(Float3::zero());
+                self.missing_normals.push(index);
+            }
+        }
+        self.vertices.insert(corner, index);
+        index
+    }
+
+    fn finish(mut self) -> Option<Mesh> {
+        if self.mesh.indices.is_empty() {
+            return None;
+        }
+        if !self.missing_normals.is_empty() {
+            let provided = std::mem::take(&mut self.mesh.normals);
+            self.mesh.generate_normals();
+            let generated = std::mem::replace(&mut self.mesh.normals, provided);
+            for &index in &self.missing_normals {
+                self.mesh.normals[index as usize] = generated[index as usize];
+            }
+        }
+        self.mesh.generate_tangents();
+        Some(self.mesh)
+    }
+}
+
+impl Model {
+    pub fn from_obj(path: &Path) -> Model {
+        let source = std::fs::read_to_string(path).unwrap();
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let name = path.file_stem().map(|name| name.to_string_lossy().into_owned());
+        Self::parse_obj(&source, directory, name)
+    }
+
+    // Material libraries and textures are resolved relative to `directory`.
+    fn parse_obj(source: &str, directory: &Path, name: Option<String>) -> Model {
+        let mut attributes = Attributes::default();
+
+        let mut textures: Vec<Texture> = vec![];
+        let mut texture_indices: HashMap<PathBuf, usize> = HashMap::new();
+        let mut materials: HashMap<String, Material> = HashMap::new();
+        let mut current_material = Material::default();
+
+        let mut meshes: Vec<Mesh> = vec![];
+        let mut builder = MeshBuilder::new(&current_material);
+
+        for line in source.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+
+            match keyword {
+                "v" => {
+                    let values: Vec<&str> = tokens.collect();
+                    let v = parse_floats::<3>(values.iter().copied());
+                    attributes.positions.push(Float3::new(v[0], v[1], v[2]));
+
+                    // Vertex colors are a common extension: `v x y z r g b`.
+                    if values.len() >= 6 {
+                        let c = parse_floats::<3>(values[3..].iter().copied());
+                        attributes
+                            .colors
+                            .resize(attributes.positions.len() - 1, Float4::new(1.0, 1.0, 1.0, 1.0));
+                        attributes.colors.push(Float4::new(c[0], c[1], c[2], 1.0));
+                    } else if !attributes.colors.is_empty() {
+                        attributes.colors.push(Float4::new(1.0, 1.0, 1.0, 1.0));
+                    }
+                }
+                "vt" => {
+                    let vt = parse_floats::<2>(tokens);
+                    // OBJ puts the texture origin at the bottom left, the texture sampler at the top left.
+                    attributes.uvs.push(Float2::new(vt[0], 1.0 - vt[1]));
+                }
+                "vn" => {
+                    let vn = parse_floats::<3>(tokens);
+                    attributes.normals.push(Float3::new(vn[0], vn[1], vn[2]));
+                }
+                "f" => {
+                    let Some(corners) = tokens
+                        .map(|corner| parse_corner(corner, &attributes))
+                        .collect::<Option<Vec<Corner>>>()
+                    else {
+                        eprintln!("Skipping face with an invalid corner: {line}");
+                        continue;
+                    };
+                    if corners.len() < 3 {
+                        continue;
+                    }
+
+                    let indices: Vec<u32> = corners
+                        .into_iter()
+                        .map(|corner| builder.vertex(corner, &attributes))
+                        .collect();
+                    for i in 1..indices.len() - 1 {
+                        builder
+                            .mesh
+                            .indices
+                            .extend_from_slice(&[indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+                "o" | "g" => {
+                    meshes.extend(builder.finish());
+                    builder = MeshBuilder::new(&current_material);
+                }
+                "usemtl" => {
+                    current_material = materials.get(rest(line)).cloned().unwrap_or_default();
+                    meshes.extend(builder.finish());
+                    builder = MeshBuilder::new(&current_material);
+                }
+                "mtllib" => {
+                    let mtl_path = directory.join(rest(line));
+                    load_materials(&mtl_path, &mut materials, &mut textures, &mut texture_indices);
+                }
+                _ => {}
+            }
+        }
+        meshes.extend(builder.finish());
+
+        let nodes = vec![Node {
+            name,
+            parent: None,
+            children: vec![],
+            transform: Transform::default(),
+            meshes: (0..meshes.len()).collect(),
+            skin: None,
+            morph_weights: vec![],
+        }];
+
+        Model {
+            meshes,
+            textures,
+            nodes,
+            skins: vec![],
+            animations: vec![],
+        }
+    }
+}
+
+fn load_materials(
+    path: &Path,
+    materials: &mut HashMap<String, Material>,
+    textures: &mut Vec<Texture>,
+    texture_indices: &mut HashMap<PathBuf, usize>,
+) {
+    let Ok(source) = std::fs::read_to_string(path) else {
+        eprintln!("Failed to read material library: {}", path.display());
+        return;
+    };
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let mut load_texture = |arguments: &str| -> Option<usize> {
+        // Texture options such as `-bm 1.0` come before the file name.
+        let file = arguments.split_whitespace().last()?;
+        let texture_path = directory.join(file.replace('\\', "/"));
+        if let Some(&index) = texture_indices.get(&texture_path) {
+            return Some(index);
+        }
+        if !texture_path.exists() {
+            eprintln!("Missing texture: {}", texture_path.display());
+            return None;
+        }
+        textures.push(Texture::from_file(&texture_path));
+        texture_indices.insert(texture_path, textures.len() - 1);
+        Some(textures.len() - 1)
+    };
+
+    let mut current: Option<(String, Material)> = None;
+    for line in source.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some(keyword) = line.split_whitespace().next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((rest(line).to_owned(), Material::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            continue;
+        };
+        match keyword {
+            "map_Kd" => material.albedo_texture_index = load_texture(rest(line)),
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                material.normal_texture_index = load_texture(rest(line))
+            }
+            "map_Ke" => material.emissive_texture_index = load_texture(rest(line)),
+            _ => {}
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+}
+
+fn rest(line: &str) -> &str {
+    line.split_once(char::is_whitespace)
+        .map(|(_, rest)| rest.trim())
+        .unwrap_or("")
+}
+
+fn parse_floats<'a, const N: usize>(tokens: impl Iterator<Item = &'a str>) -> [f32; N] {
+    let mut values = [0.0; N];
+    for (value, token) in values.iter_mut().zip(tokens) {
+        *value = token.parse().unwrap_or(0.0);
+    }
+    values
+}
+
+// OBJ indices are one based, negative indices count back from the last element.
+fn resolve_index(token: &str, count: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    (0..count as i64).contains(&resolved).then_some(resolved as usize)
+}
+
+fn parse_corner(corner: &str, attributes: &Attributes) -> Option<Corner> {
+    let mut parts = corner.split('/');
+    let position = resolve_index(parts.next()?, attributes.positions.len())?;
+    let uv = parts.next().and_then(|uv| resolve_index(uv, attributes.uvs.len()));
+    let normal = parts
+        .next()
+        .and_then(|normal| resolve_index(normal, attributes.normals.len()));
+    Some((position, uv, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Model {
+        Model::parse_obj(source, Path::new(""), None)
+    }
+
+    fn assert_close(a: Float3, b: Float3) {
+        assert!((a - b).length() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn polygons_are_fanned_into_triangles() {
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+             f 1/1 2/2 3/3 4/4\n",
+        );
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.positions.len(), 4);
+        // The v axis is flipped to a top left origin.
+        assert_eq!(mesh.uvs[3].y, 0.0);
+        assert_eq!(mesh.uvs[0].y, 1.0);
+    }
+
+    #[test]
+    fn corners_are_shared_and_negative_indices_count_back() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf -4 -2 -1\n");
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.positions.len(), 4);
+    }
+
+    #[test]
+    fn faces_with_out_of_range_corners_are_skipped() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3 9\nf 1 2 7\nf 3 2 1\n");
+        assert_eq!(model.meshes[0].indices, vec![0, 1, 2]);
+        assert_eq!(model.meshes[0].positions.len(), 3);
+    }
+
+    #[test]
+    fn only_missing_normals_are_generated() {
+        // The first triangle has a deliberately skewed normal that must survive as given.
+        let model = parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vn 1 0 0\n\
+             f 1//1 2//1 3//1\n\
+             f 1 3 4\n",
+        );
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.positions.len(), 6);
+        for vertex in 0..3 {
+            assert_close(mesh.normals[vertex], Float3::new(1.0, 0.0, 0.0));
+        }
+        for vertex in 3..6 {
+            assert_close(mesh.normals[vertex], Float3::new(0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn vertex_colors_default_to_white() {
+        let model = parse("v 0 0 0\nv 1 0 0 1 0 0\nv 1 1 0\nf 1 2 3\n");
+        let colors = &model.meshes[0].colors;
+        assert_eq!(colors.len(), 3);
+        assert_eq!((colors[0].x, colors[0].y), (1.0, 1.0));
+        assert_eq!((colors[1].x, colors[1].y), (1.0, 0.0));
+        assert_eq!((colors[2].x, colors[2].y), (1.0, 1.0));
+    }
+
+    #[test]
+    fn colors_stay_aligned_when_they_start_after_a_face() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\nv 0 1 0 0 0 1\nf 1 3 4\n");
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.colors.len(), mesh.positions.len());
+        assert_eq!((mesh.colors[0].x, mesh.colors[0].z), (1.0, 1.0));
+        assert_eq!((mesh.colors[3].x, mesh.colors[3].z), (0.0, 1.0));
+    }
+
+    #[test]
+    fn materials_load_textures_relative_to_the_library() {
+        let root = std::env::temp_dir().join(format!("obj_mtl_test_{}", std::process::id()));
+        let materials = root.join("materials");
+        std::fs::create_dir_all(materials.join("textures")).unwrap();
+        std::fs::write(
+            materials.join("scene.mtl"),
+            "newmtl red\nKd 1 0 0\nmap_Kd -bm 1.0 textures\\red.ppm\nnewmtl plain\n",
+        )
+        .unwrap();
+        let mut ppm = b"P6\n1 1\n255\n".to_vec();
+        ppm.extend_from_slice(&[255, 0, 0]);
+        std::fs::write(materials.join("textures/red.ppm"), ppm).unwrap();
+        std::fs::write(
+            root.join("scene.obj"),
+            "mtllib materials/scene.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\n\
+             usemtl red\nf 1 2 3\nusemtl plain\nf 3 2 1\nusemtl red\nf 1 3 2\n",
+        )
+        .unwrap();
+
+        let model = Model::from_obj(&root.join("scene.obj"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // Both uses of the red material share one texture.
+        assert_eq!(model.textures.len(), 1);
+        assert_eq!(model.meshes.len(), 3);
+        assert_eq!(model.meshes[0].albedo_texture_index, Some(0));
+        assert_eq!(model.meshes[1].albedo_texture_index, None);
+        assert_eq!(model.meshes[2].albedo_texture_index, Some(0));
+        assert_eq!((model.textures[0].width, model.textures[0].height), (1, 1));
+    }
+
+    #[test]
+    fn groups_start_new_meshes() {
+        let model = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\no a\nf 1 2 3\ng b\nf 3 2 1\ng empty\n");
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.nodes[0].meshes, vec![0, 1]);
+    }
+}
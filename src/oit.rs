@@ -0,0 +1,312 @@
+use crate::command::{FragmentBuiltins, Shader};
+use crate::format::PixelFormat;
+use crate::framebuffer::{Attachment, Attachments};
+use crate::image_view::Image;
+use crate::math::{Color, Float4};
+
+// A translucent fragment in linear space with straight alpha, plus the depth it was shaded at.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TranslucentFragment {
+    pub color: Float4,
+    pub depth: f32,
+}
+
+// Draws any shader writing linear colors as translucent, for use with an `OitBuffer` target.
+// Opaque and translucent draws only differ in the shader wrapper and framebuffer they use.
+pub struct Translucent<S>(pub S);
+
+impl<S: Shader<Output = Float4>> Shader for Translucent<S> {
+    type VertexInput = S::VertexInput;
+    type Varyings = S::Varyings;
+    type Uniforms = S::Uniforms;
+    type Output = TranslucentFragment;
+
+    const MAY_DISCARD: bool = S::MAY_DISCARD;
+    const WRITES_DEPTH: bool = S::WRITES_DEPTH;
+
+    fn vertex(
+        &self,
+        vertex_index: u32,
+        input: &Self::VertexInput,
+        uniforms: &Self::Uniforms,
+    ) -> (Self::Varyings, Float4) {
+        self.0.vertex(vertex_index, input, uniforms)
+    }
+
+    fn fragment(
+        &self,
+        varyings: &Self::Varyings,
+        builtins: &FragmentBuiltins,
+        uniforms: &Self::Uniforms,
+    ) -> Option<TranslucentFragment> {
+        self.fragment_with_depth(varyings, builtins, uniforms)
+            .map(|(fragment, _)| fragment)
+    }
+
+    fn fragment_with_depth(
+        &self,
+        varyings: &Self::Varyings,
+        builtins: &FragmentBuiltins,
+        uniforms: &Self::Uniforms,
+    ) -> Option<(TranslucentFragment, f32)> {
+        self.0
+            .fragment_with_depth(varyings, builtins, uniforms)
+            .map(|(color, depth)| (TranslucentFragment { color, depth }, depth))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OitMode {
+    // Order independent by construction, approximate where many layers overlap.
+    WeightedBlended,
+    // Keeps the k nearest fragments per pixel and merges anything beyond into the farthest one.
+    KBuffer(usize),
+}
+
+pub struct OitBuffer {
+    pub mode: OitMode,
+    pub width: u32,
+    pub height: u32,
+    accumulation: Vec<Float4>,
+    revealage: Vec<f32>,
+    fragments: Vec<TranslucentFragment>,
+    counts: Vec<usize>,
+}
+
+impl OitBuffer {
+    pub fn new(width: u32, height: u32, mode: OitMode) -> Self {
+        let pixels = (width * height) as usize;
+        let mut buffer = Self {
+            mode,
+            width,
+            height,
+            accumulation: vec![],
+            revealage: vec![],
+            fragments: vec![],
+            counts: vec![],
+        };
+        match mode {
+            OitMode::WeightedBlended => {
+                buffer.accumulation = vec![Float4::zero(); pixels];
+                buffer.revealage = vec![1.0; pixels];
+            }
+            OitMode::KBuffer(k) => {
+                buffer.fragments = vec![TranslucentFragment::default(); pixels * k];
+                buffer.counts = vec![0; pixels];
+            }
+        }
+        buffer
+    }
+
+    pub fn clear(&mut self) {
+        self.accumulation.fill(Float4::zero());
+        self.revealage.fill(1.0);
+        self.counts.fill(0);
+    }
+
+    fn insert(&mut self, x: u32, y: u32, fragment: TranslucentFragment) {
+        let index = (y * self.width + x) as usize;
+        let alpha = fragment.color.w.clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return;
+        }
+
+        match self.mode {
+            OitMode::WeightedBlended => {
+                // McGuire and Bavoil's depth weight, nearer fragments dominate the average.
+                let weight = alpha * (3e3 * (1.0 - fragment.depth).powi(3)).clamp(1e-2, 3e3);
+                let color = fragment.color;
+                self.accumulation[index] = self.accumulation[index]
+                    + Float4::new(color.x * alpha, color.y * alpha, color.z * alpha, alpha) * weight;
+                self.revealage[index] *= 1.0 - alpha;
+            }
+            OitMode::KBuffer(k) => {
+                if k == 0 {
+                    return;
+                }
+                let slots = &mut self.fragments[index * k..(index + 1) * k];
+                let count = &mut self.counts[index];
+
+                // Slots stay sorted front to back.
+                let position = slots[..*count].partition_point(|stored| stored.depth <= fragment.depth);
+                if *count < k {
+                    slots[position..=*count].rotate_right(1);
+                    slots[position] = fragment;
+                    *count += 1;
+                } else if position == k {
+                    slots[k - 1] = composite_over(slots[k - 1], fragment);
+                } else {
+                    let evicted = slots[k - 1];
+                    slots[position..].rotate_right(1);
+                    slots[position] = fragment;
+                    slots[k - 1] = composite_over(slots[k - 1], evicted);
+                }
+            }
+        }
+    }
+
+    // Composites the translucent layers over the opaque image already in `target`.
+    pub fn resolve<P: PixelFormat>(&self, target: &mut Image<P>) {
+        let width = self.width.min(target.width);
+        let height = self.height.min(target.height);
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * self.width + x) as usize;
+                let background = target.get_linear(x, y);
+                let color = match self.mode {
+                    OitMode::WeightedBlended => {
+                        let revealage = self.revealage[index];
+                        if revealage >= 1.0 {
+                            continue;
+                        }
+                        let accumulation = self.accumulation[index];
+                        let average = (1.0 / accumulation.w.max(1e-5)) * accumulation;
+                        (1.0 - revealage) * average + revealage * background
+                    }
+                    OitMode::KBuffer(k) => {
+                        let count = self.counts[index];
+                        if count == 0 {
+                            continue;
+                        }
+                        self.fragments[index * k..index * k + count]
+                            .iter()
+                            .rev()
+                            .fold(background, |below, fragment| {
+                                let alpha = fragment.color.w.clamp(0.0, 1.0);
+                                alpha * fragment.color + (1.0 - alpha) * below
+                            })
+                    }
+                };
+                target.set_linear(x, y, Float4::new(color.x, color.y, color.z, background.w));
+            }
+        }
+    }
+}
+
+// Merges two straight alpha fragments into one, `front` in front of `back`.
+fn composite_over(front: TranslucentFragment, back: TranslucentFragment) -> TranslucentFragment {
+    let front_alpha = front.color.w.clamp(0.0, 1.0);
+    let back_alpha = back.color.w.clamp(0.0, 1.0) * (1.0 - front_alpha);
+    let alpha = front_alpha + back_alpha;
+    if alpha <= 0.0 {
+        return front;
+    }
+    let color = (1.0 / alpha) * (front_alpha * front.color + back_alpha * back.color);
+    TranslucentFragment {
+        color: Float4::new(color.x, color.y, color.z, alpha),
+        depth: front.depth,
+    }
+}
+
+impl Attachment for OitBuffer {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn write_color(&mut self, x: u32, y: u32, color: Color) {
+        self.insert(
+            x,
+            y,
+            TranslucentFragment {
+                color: Float4::from(color),
+                depth: 0.0,
+            },
+        );
+    }
+}
+
+impl Attachments<TranslucentFragment> for OitBuffer {
+    fn write(&mut self, x: u32, y: u32, output: TranslucentFragment) {
+        self.insert(x, y, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(color: Float4, depth: f32) -> TranslucentFragment {
+        TranslucentFragment { color, depth }
+    }
+
+    fn depths(buffer: &OitBuffer) -> Vec<f32> {
+        buffer.fragments[..buffer.counts[0]].iter().map(|f| f.depth).collect()
+    }
+
+    fn approx(a: Float4, b: Float4) -> bool {
+        [a.x - b.x, a.y - b.y, a.z - b.z, a.w - b.w].iter().all(|d| d.abs() < 1e-5)
+    }
+
+    const RED: Float4 = Float4::new(1.0, 0.0, 0.0, 0.5);
+    const GREEN: Float4 = Float4::new(0.0, 1.0, 0.0, 0.5);
+    const BLUE: Float4 = Float4::new(0.0, 0.0, 1.0, 0.5);
+
+    #[test]
+    fn k_buffer_keeps_fragments_sorted_front_to_back() {
+        let mut buffer = OitBuffer::new(1, 1, OitMode::KBuffer(3));
+        for depth in [0.5, 0.2, 0.8] {
+            buffer.insert(0, 0, fragment(RED, depth));
+        }
+        assert_eq!(depths(&buffer), vec![0.2, 0.5, 0.8]);
+
+        buffer.insert(0, 0, fragment(Float4::new(1.0, 1.0, 1.0, 0.0), 0.1));
+        assert_eq!(buffer.counts[0], 3, "fully transparent fragments are dropped");
+    }
+
+    #[test]
+    fn k_buffer_merges_overflow_into_the_farthest_slot() {
+        let mut buffer = OitBuffer::new(1, 1, OitMode::KBuffer(2));
+        buffer.insert(0, 0, fragment(RED, 0.2));
+        buffer.insert(0, 0, fragment(GREEN, 0.8));
+        // Nearer than the last slot, so it takes that slot and the evicted green merges behind it.
+        buffer.insert(0, 0, fragment(BLUE, 0.5));
+        assert_eq!(depths(&buffer), vec![0.2, 0.5]);
+        let merged = buffer.fragments[1].color;
+        assert!(approx(merged, Float4::new(0.0, 1.0 / 3.0, 2.0 / 3.0, 0.75)), "{merged:?}");
+
+        // Farther than every slot, so it merges behind the last one.
+        buffer.insert(0, 0, fragment(Float4::new(0.0, 0.0, 0.0, 1.0), 0.9));
+        let merged = buffer.fragments[1];
+        assert_eq!(merged.depth, 0.5);
+        assert!((merged.color.w - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn k_buffer_resolve_composites_back_to_front() {
+        let mut buffer = OitBuffer::new(2, 1, OitMode::KBuffer(4));
+        buffer.insert(0, 0, fragment(BLUE, 0.6));
+        buffer.insert(0, 0, fragment(RED, 0.3));
+
+        let background = Float4::new(0.0, 0.0, 0.0, 1.0);
+        let mut target: Image<Float4> = Image::new(2, 1);
+        target.clear_image(background);
+        buffer.resolve(&mut target);
+        assert!(approx(target.pixels[0], Float4::new(0.5, 0.0, 0.25, 1.0)), "{:?}", target.pixels[0]);
+        assert!(approx(target.pixels[1], background));
+    }
+
+    #[test]
+    fn weighted_blended_resolve_is_order_independent() {
+        let background = Float4::new(0.0, 0.0, 1.0, 1.0);
+        let resolve = |fragments: &[TranslucentFragment]| {
+            let mut buffer = OitBuffer::new(2, 1, OitMode::WeightedBlended);
+            for &fragment in fragments {
+                buffer.insert(0, 0, fragment);
+            }
+            let mut target: Image<Float4> = Image::new(2, 1);
+            target.clear_image(background);
+            buffer.resolve(&mut target);
+            target.pixels
+        };
+
+        let single = resolve(&[fragment(RED, 0.5)]);
+        assert!(approx(single[0], Float4::new(0.5, 0.0, 0.5, 1.0)), "{:?}", single[0]);
+        assert!(approx(single[1], background));
+
+        let front_first = resolve(&[fragment(RED, 0.3), fragment(GREEN, 0.6)]);
+        let back_first = resolve(&[fragment(GREEN, 0.6), fragment(RED, 0.3)]);
+        assert!(approx(front_first[0], back_first[0]));
+        // Both layers together let a quarter of the background through.
+        assert!((front_first[0].z - 0.25).abs() < 1e-5);
+    }
+}
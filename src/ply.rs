@@ -0,0 +1,481 @@
+use crate::math::{Float2, Float3, Float4};
+use crate::meshes::Mesh;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::I8),
+            "uchar" | "uint8" => Some(Self::U8),
+            "short" | "int16" => Some(Self::I16),
+            "ushort" | "uint16" => Some(Self::U16),
+            "int" | "int32" => Some(Self::I32),
+            "uint" | "uint32" => Some(Self::U32),
+            "float" | "float32" => Some(Self::F32),
+            "double" | "float64" => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        !matches!(self, Self::F32 | Self::F64)
+    }
+}
+
+enum PropertyType {
+    Scalar(ScalarType),
+    List(ScalarType, ScalarType),
+}
+
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+enum Value {
+    Scalar(f64),
+    List(Vec<f64>),
+}
+
+impl Value {
+    fn scalar(&self) -> f64 {
+        match self {
+            Value::Scalar(value) => *value,
+            Value::List(values) => values.first().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+struct Reader<'a> {
+    format: Format,
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    // Every binary value takes its full size, every ASCII value at least one byte. Checking counts
+    // against this up front keeps a corrupted count from looping or allocating far past the data.
+    fn ensure_available(&self, count: usize, ascii_size: usize, binary_size: usize) -> Result<(), String> {
+        let size = if self.format == Format::Ascii { ascii_size } else { binary_size };
+        let needed = count.saturating_mul(size);
+        let available = self.data.len() - self.offset;
+        if needed > available {
+            return Err(format!("{count} values need {needed} bytes but only {available} are left"));
+        }
+        Ok(())
+    }
+
+    fn ascii_token(&mut self) -> Result<&str, String> {
+        let rest = &self.data[self.offset..];
+        let start = rest
+            .iter()
+            .position(|c| !c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let length = rest[start..]
+            .iter()
+            .position(|c| c.is_ascii_whitespace())
+            .unwrap_or(rest.len() - start);
+        if length == 0 {
+            return Err("unexpected end of data".to_owned());
+        }
+        self.offset += start + length;
+        Ok(std::str::from_utf8(&rest[start..start + length]).unwrap_or(""))
+    }
+
+    fn read(&mut self, ty: ScalarType) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            return Ok(self.ascii_token()?.parse().unwrap_or(0.0));
+        }
+
+        let size = ty.size();
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + size)
+            .ok_or("unexpected end of data")?;
+        self.offset += size;
+
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+        Ok(match ty {
+            ScalarType::I8 => buffer[0] as i8 as f64,
+            ScalarType::U8 => buffer[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            ScalarType::U32 => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            ScalarType::F32 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            ScalarType::F64 => f64::from_le_bytes(buffer),
+        })
+    }
+
+    fn read_row(&mut self, element: &Element) -> Result<Vec<Value>, String> {
+        element
+            .properties
+            .iter()
+            .map(|property| match property.ty {
+                PropertyType::Scalar(ty) => Ok(Value::Scalar(self.read(ty)?)),
+                PropertyType::List(count_ty, item_ty) => {
+                    let count = self.read(count_ty)?;
+                    if count < 0.0 {
+                        return Err(format!("negative list length {count}"));
+                    }
+                    let count = count as usize;
+                    self.ensure_available(count, 1, item_ty.size())?;
+                    let items = (0..count).map(|_| self.read(item_ty)).collect::<Result<_, _>>()?;
+                    Ok(Value::List(items))
+                }
+            })
+            .collect()
+    }
+
+    fn ensure_rows(&self, element: &Element) -> Result<(), String> {
+        // Lists count as just their length here, their items are checked once it is known.
+        let binary_size = element
+            .properties
+            .iter()
+            .map(|property| match property.ty {
+                PropertyType::Scalar(ty) | PropertyType::List(ty, _) => ty.size(),
+            })
+            .sum();
+        self.ensure_available(element.count, element.properties.len(), binary_size)
+            .map_err(|error| format!("element {}: {error}", element.name))
+    }
+}
+
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+    let end = data
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or("no end_header")?;
+    let body_start = data[end..]
+        .iter()
+        .position(|&c| c == b'\n')
+        .map(|newline| end + newline + 1)
+        .unwrap_or(data.len());
+
+    let header = String::from_utf8_lossy(&data[..end]);
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("not a PLY file".to_owned());
+    }
+
+    let mut format = Format::Ascii;
+    let mut elements: Vec<Element> = vec![];
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", "ascii", ..] => format = Format::Ascii,
+            ["format", "binary_little_endian", ..] => format = Format::BinaryLittleEndian,
+            ["format", "binary_big_endian", ..] => format = Format::BinaryBigEndian,
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid count for element {name}: {count}"))?,
+                properties: vec![],
+            }),
+            // Skipping a property of unknown size would misalign every following value.
+            ["property", "list", count_ty, item_ty, name] => {
+                let element = elements.last_mut().ok_or("property before any element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::List(scalar_type(count_ty)?, scalar_type(item_ty)?),
+                });
+            }
+            ["property", ty, name] => {
+                let element = elements.last_mut().ok_or("property before any element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::Scalar(scalar_type(ty)?),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok((format, elements, body_start))
+}
+
+fn scalar_type(name: &str) -> Result<ScalarType, String> {
+    ScalarType::parse(name).ok_or_else(|| format!("unknown property type {name}"))
+}
+
+fn property_index(element: &Element, names: &[&str]) -> Option<usize> {
+    element
+        .properties
+        .iter()
+        .position(|property| names.contains(&property.name.as_str()))
+}
+
+impl Mesh {
+    // Point clouds (no face element) come back with empty indices. Malformed files are reported
+    // and come back empty.
+    pub fn from_ply(path: &Path) -> Mesh {
+        let data = std::fs::read(path).unwrap();
+        Self::parse_ply(&data).unwrap_or_else(|error| {
+            eprintln!("Failed to load {}: {error}", path.display());
+            Mesh::default()
+        })
+    }
+
+    fn parse_ply(data: &[u8]) -> Result<Mesh, String> {
+        let (format, elements, body_start) = parse_header(data)?;
+        let mut reader = Reader {
+            format,
+            data,
+            offset: body_start,
+        };
+
+        let mut mesh = Mesh::default();
+        let mut has_normals = false;
+        let mut has_uvs = false;
+
+        for element in &elements {
+            match element.name.as_str() {
+                "vertex" => {
+                    let position = ["x", "y", "z"].map(|name| property_index(element, &[name]));
+                    let normal = ["nx", "ny", "nz"].map(|name| property_index(element, &[name]));
+                    let uv = [
+                        property_index(element, &["u", "s", "texture_u", "texture_s"]),
+                        property_index(element, &["v", "t", "texture_v", "texture_t"]),
+                    ];
+                    let color = [
+                        property_index(element, &["red", "r", "diffuse_red"]),
+                        property_index(element, &["green", "g", "diffuse_green"]),
+                        property_index(element, &["blue", "b", "diffuse_blue"]),
+                        property_index(element, &["alpha", "a"]),
+                    ];
+                    // Integer colors are 0-255, floating point colors already 0-1.
+                    let color_scale = color[0]
+                        .map(|index| match element.properties[index].ty {
+                            PropertyType::Scalar(ty) if ty.is_integer() => 1.0 / 255.0,
+                            _ => 1.0,
+                        })
+                        .unwrap_or(1.0);
+
+                    has_normals = normal.iter().all(Option::is_some);
+                    has_uvs = uv.iter().all(Option::is_some);
+                    let has_colors = color[..3].iter().all(Option::is_some);
+
+                    reader.ensure_rows(element)?;
+                    for _ in 0..element.count {
+                        let row = reader.read_row(element)?;
+                        let get = |index: Option<usize>| {
+                            index.map(|index| row[index].scalar() as f32).unwrap_or(0.0)
+                        };
+
+                        mesh.positions
+                            .push(Float3::new(get(position[0]), get(position[1]), get(position[2])));
+                        if has_normals {
+                            mesh.normals
+                                .push(Float3::new(get(normal[0]), get(normal[1]), get(normal[2])));
+                        }
+                        if has_uvs {
+                            mesh.uvs.push(Float2::new(get(uv[0]), 1.0 - get(uv[1])));
+                        }
+                        if has_colors {
+                            let alpha = color[3]
+                                .map(|_| get(color[3]) * color_scale)
+                                .unwrap_or(1.0);
+                            mesh.colors.push(Float4::new(
+                                get(color[0]) * color_scale,
+                                get(color[1]) * color_scale,
+                                get(color[2]) * color_scale,
+                                alpha,
+                            ));
+                        }
+                    }
+                }
+                "face" => {
+                    let indices = property_index(element, &["vertex_indices", "vertex_index"]);
+                    reader.ensure_rows(element)?;
+                    for _ in 0..element.count {
+                        let row = reader.read_row(element)?;
+                        let Some(Value::List(face)) = indices.map(|index| &row[index]) else {
+                            continue;
+                        };
+                        // Negative indices map to u32::MAX and fail the range check below.
+                        let index = |i: usize| if face[i] >= 0.0 { face[i] as u32 } else { u32::MAX };
+                        for i in 1..face.len().saturating_sub(1) {
+                            mesh.indices.extend_from_slice(&[index(0), index(i), index(i + 1)]);
+                        }
+                    }
+                }
+                _ => {
+                    reader.ensure_rows(element)?;
+                    for _ in 0..element.count {
+                        reader.read_row(element)?;
+                    }
+                }
+            }
+        }
+
+        // Faces may come before the vertices, so indices are only checked once both are read.
+        if let Some(&index) = mesh.indices.iter().find(|&&index| index as usize >= mesh.positions.len()) {
+            return Err(format!(
+                "face references vertex {index} but there are only {}",
+                mesh.positions.len()
+            ));
+        }
+
+        if !has_uvs {
+            mesh.uvs = vec![Float2::zero(); mesh.positions.len()];
+        }
+        if !mesh.indices.is_empty() {
+            if !has_normals {
+                mesh.generate_normals();
+            }
+            mesh.generate_tangents();
+        }
+
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "element vertex 4\n\
+        property float x\n\
+        property float y\n\
+        property float z\n\
+        property uchar red\n\
+        property uchar green\n\
+        property uchar blue\n\
+        element face 1\n\
+        property list uchar int vertex_indices\n\
+        end_header\n";
+
+    const POSITIONS: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+
+    fn binary_quad(format: &str, big_endian: bool) -> Vec<u8> {
+        let mut data = format!("ply\nformat {format} 1.0\n{HEADER}").into_bytes();
+        for position in POSITIONS {
+            for value in position {
+                data.extend(if big_endian { value.to_be_bytes() } else { value.to_le_bytes() });
+            }
+            data.extend([255, 0, 128]);
+        }
+        data.push(4);
+        for index in [0i32, 1, 2, 3] {
+            data.extend(if big_endian { index.to_be_bytes() } else { index.to_le_bytes() });
+        }
+        data
+    }
+
+    fn assert_quad(mesh: &Mesh) {
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.positions.len(), 4);
+        for (position, expected) in mesh.positions.iter().zip(POSITIONS) {
+            assert_eq!([position.x, position.y, position.z], expected);
+        }
+        let color = mesh.colors[2];
+        assert_eq!([color.x, color.y, color.z, color.w], [1.0, 0.0, 128.0 / 255.0, 1.0]);
+        // No normals in the file, so they are generated from the counter-clockwise faces.
+        assert!((mesh.normals[0].z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let source = format!(
+            "ply\nformat ascii 1.0\ncomment test\n{HEADER}\
+             0 0 0 255 0 128\n1 0 0 255 0 128\n1 1 0 255 0 128\n0 1 0 255 0 128\n\
+             4 0 1 2 3\n"
+        );
+        assert_quad(&Mesh::parse_ply(source.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_little_endian() {
+        assert_quad(&Mesh::parse_ply(&binary_quad("binary_little_endian", false)).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_big_endian() {
+        assert_quad(&Mesh::parse_ply(&binary_quad("binary_big_endian", true)).unwrap());
+    }
+
+    #[test]
+    fn rejects_out_of_range_faces() {
+        let face = |indices: &str| {
+            format!(
+                "ply\nformat ascii 1.0\n{HEADER}\
+                 0 0 0 0 0 0\n1 0 0 0 0 0\n1 1 0 0 0 0\n0 1 0 0 0 0\n{indices}\n"
+            )
+        };
+        assert!(Mesh::parse_ply(face("3 0 1 4").as_bytes()).is_err());
+        assert!(Mesh::parse_ply(face("3 0 -1 2").as_bytes()).is_err());
+        assert!(Mesh::parse_ply(face("3 0 1 3").as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        assert!(Mesh::parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\n").is_err());
+        assert!(Mesh::parse_ply(b"obj\nend_header\n").is_err());
+        assert!(Mesh::parse_ply(b"ply\nelement vertex many\nend_header\n").is_err());
+        assert!(Mesh::parse_ply(b"ply\nelement vertex 1\nproperty half x\nend_header\n").is_err());
+    }
+
+    #[test]
+    fn rejects_counts_beyond_the_data() {
+        let mut data = binary_quad("binary_little_endian", false);
+        let truncated = data[..data.len() - 1].to_vec();
+        assert!(Mesh::parse_ply(&truncated).is_err());
+
+        // The face list claims 200 indices but only 4 follow.
+        let list_length = data.len() - 17;
+        data[list_length] = 200;
+        assert!(Mesh::parse_ply(&data).is_err());
+
+        let huge = b"ply\nformat binary_little_endian 1.0\nelement vertex 4000000000000\n\
+            property float x\nend_header\n\0\0\0\0";
+        assert!(Mesh::parse_ply(huge).is_err());
+
+        let ascii = format!("ply\nformat ascii 1.0\n{HEADER}0 0 0 255 0 128\n1 0 0 255 0 128\n");
+        assert!(Mesh::parse_ply(ascii.as_bytes()).is_err());
+        let ascii = format!(
+            "ply\nformat ascii 1.0\n{HEADER}\
+             0 0 0 0 0 0\n1 0 0 0 0 0\n1 1 0 0 0 0\n0 1 0 0 0 0\n\
+             999999999 0 1 2\n"
+        );
+        assert!(Mesh::parse_ply(ascii.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,489 @@
+use crate::format::PixelFormat;
+use crate::image_view::{Image, RenderTarget, RenderTargetRgba32F, Texture};
+use crate::math::{Color, Float3, Float4};
+
+// Full-screen effects read one linear RGBA32F image and write another of the same size. Nothing
+// here touches the window, so the stack also runs headless on offscreen renders.
+pub struct PostStack {
+    pub effects: Vec<PostPass>,
+    front: RenderTargetRgba32F,
+    back: RenderTargetRgba32F,
+}
+
+pub struct PostPass {
+    pub effect: PostEffect,
+    pub enabled: bool,
+}
+
+pub enum PostEffect {
+    Fxaa(Fxaa),
+    Bloom(Bloom),
+    Vignette(Vignette),
+    ChromaticAberration(ChromaticAberration),
+    Sharpen(Sharpen),
+}
+
+impl PostEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Fxaa(_) => "fxaa",
+            PostEffect::Bloom(_) => "bloom",
+            PostEffect::Vignette(_) => "vignette",
+            PostEffect::ChromaticAberration(_) => "chromatic_aberration",
+            PostEffect::Sharpen(_) => "sharpen",
+        }
+    }
+
+    pub fn apply(&mut self, source: &RenderTargetRgba32F, target: &mut RenderTargetRgba32F) {
+        match self {
+            PostEffect::Fxaa(effect) => effect.apply(source, target),
+            PostEffect::Bloom(effect) => effect.apply(source, target),
+            PostEffect::Vignette(effect) => effect.apply(source, target),
+            PostEffect::ChromaticAberration(effect) => effect.apply(source, target),
+            PostEffect::Sharpen(effect) => effect.apply(source, target),
+        }
+    }
+}
+
+impl Default for PostStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PostStack {
+    pub fn new() -> Self {
+        Self {
+            effects: vec![],
+            front: Image::new(0, 0),
+            back: Image::new(0, 0),
+        }
+    }
+
+    pub fn push(&mut self, effect: PostEffect) {
+        self.effects.push(PostPass {
+            effect,
+            enabled: true,
+        });
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        for pass in self.effects.iter_mut().filter(|pass| pass.effect.name() == name) {
+            pass.enabled = enabled;
+        }
+    }
+
+    pub fn effect_mut(&mut self, name: &str) -> Option<&mut PostEffect> {
+        self.effects
+            .iter_mut()
+            .find(|pass| pass.effect.name() == name)
+            .map(|pass| &mut pass.effect)
+    }
+
+    // Runs the enabled effects in order, ping-ponging between two internal buffers.
+    pub fn run<P: PixelFormat>(&mut self, source: &Image<P>) -> &RenderTargetRgba32F {
+        if (self.front.width, self.front.height) != (source.width, source.height) {
+            self.front = Image::new(source.width, source.height);
+            self.back = Image::new(source.width, source.height);
+        }
+        source.convert_into(&mut self.front);
+
+        for pass in self.effects.iter_mut().filter(|pass| pass.enabled) {
+            pass.effect.apply(&self.front, &mut self.back);
+            std::mem::swap(&mut self.front, &mut self.back);
+        }
+        &self.front
+    }
+
+    pub fn output(&self) -> &RenderTargetRgba32F {
+        &self.front
+    }
+}
+
+fn fetch(image: &RenderTargetRgba32F, x: i32, y: i32) -> Float4 {
+    let x = x.clamp(0, image.width as i32 - 1) as u32;
+    let y = y.clamp(0, image.height as i32 - 1) as u32;
+    image.pixels[(y * image.width + x) as usize]
+}
+
+// Bilinear sample in pixel coordinates, pixel centers sit at half integers.
+fn sample(image: &RenderTargetRgba32F, x: f32, y: f32) -> Float4 {
+    let x = x - 0.5;
+    let y = y - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as i32, y0 as i32);
+    let top = (1.0 - tx) * fetch(image, x0, y0) + tx * fetch(image, x0 + 1, y0);
+    let bottom = (1.0 - tx) * fetch(image, x0, y0 + 1) + tx * fetch(image, x0 + 1, y0 + 1);
+    (1.0 - ty) * top + ty * bottom
+}
+
+fn for_each_pixel(target: &mut RenderTargetRgba32F, f: impl Fn(u32, u32) -> Float4) {
+    let width = target.width;
+    for (i, pixel) in target.pixels.iter_mut().enumerate() {
+        *pixel = f(i as u32 % width, i as u32 / width);
+    }
+}
+
+fn luma(color: Float4) -> f32 {
+    Float3::new(color.x, color.y, color.z).dot(Float3::new(0.299, 0.587, 0.114))
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+pub struct Fxaa {
+    pub edge_threshold: f32,
+    pub edge_threshold_min: f32,
+    pub span_max: f32,
+}
+
+impl Default for Fxaa {
+    fn default() -> Self {
+        Self {
+            edge_threshold: 1.0 / 8.0,
+            edge_threshold_min: 1.0 / 32.0,
+            span_max: 8.0,
+        }
+    }
+}
+
+impl Fxaa {
+    pub fn apply(&self, source: &RenderTargetRgba32F, target: &mut RenderTargetRgba32F) {
+        // Luma is compressed so edges between HDR values are detected like displayed ones.
+        let perceptual = |c: Float4| {
+            let l = luma(c).max(0.0);
+            l / (1.0 + l)
+        };
+
+        for_each_pixel(target, |x, y| {
+            let (x, y) = (x as i32, y as i32);
+            let center = fetch(source, x, y);
+            let l_m = perceptual(center);
+            let l_nw = perceptual(fetch(source, x - 1, y - 1));
+            let l_ne = perceptual(fetch(source, x + 1, y - 1));
+            let l_sw = perceptual(fetch(source, x - 1, y + 1));
+            let l_se = perceptual(fetch(source, x + 1, y + 1));
+
+            let l_min = l_m.min(l_nw).min(l_ne).min(l_sw).min(l_se);
+            let l_max = l_m.max(l_nw).max(l_ne).max(l_sw).max(l_se);
+            if l_max - l_min < self.edge_threshold_min.max(l_max * self.edge_threshold) {
+                return center;
+            }
+
+            let mut dx = -((l_nw + l_ne) - (l_sw + l_se));
+            let mut dy = (l_nw + l_sw) - (l_ne + l_se);
+            let reduce = ((l_nw + l_ne + l_sw + l_se) * 0.25 * self.edge_threshold).max(1.0 / 128.0);
+            let scale = 1.0 / (dx.abs().min(dy.abs()) + reduce);
+            dx = (dx * scale).clamp(-self.span_max, self.span_max);
+            dy = (dy * scale).clamp(-self.span_max, self.span_max);
+
+            let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+            let a = 0.5
+                * (sample(source, cx - dx / 6.0, cy - dy / 6.0)
+                    + sample(source, cx + dx / 6.0, cy + dy / 6.0));
+            let b = 0.5 * a
+                + 0.25
+                    * (sample(source, cx - dx * 0.5, cy - dy * 0.5)
+                        + sample(source, cx + dx * 0.5, cy + dy * 0.5));
+
+            let l_b = perceptual(b);
+            let mut result = if l_b < l_min || l_b > l_max { a } else { b };
+            result.w = center.w;
+            result
+        });
+    }
+}
+
+pub struct Bloom {
+    // Luminance above which pixels bloom, with a soft knee of the given width.
+    pub threshold: f32,
+    pub knee: f32,
+    pub intensity: f32,
+    pub levels: u32,
+    // The bright pass followed by the blurred mips, kept between frames like the stack's buffers.
+    chain: Vec<RenderTargetRgba32F>,
+    scratch: Vec<RenderTargetRgba32F>,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.05,
+            levels: 5,
+            chain: vec![],
+            scratch: vec![],
+        }
+    }
+}
+
+impl Bloom {
+    pub fn apply(&mut self, source: &RenderTargetRgba32F, target: &mut RenderTargetRgba32F) {
+        self.resize(source.width, source.height);
+
+        for (dst, &src) in self.chain[0].pixels.iter_mut().zip(&source.pixels) {
+            let brightness = src.x.max(src.y).max(src.z);
+            let soft = (brightness - self.threshold + self.knee).clamp(0.0, 2.0 * self.knee);
+            let soft = soft * soft / (4.0 * self.knee + 1e-5);
+            let contribution = soft.max(brightness - self.threshold) / brightness.max(1e-5);
+            *dst = contribution * Float4::new(src.x, src.y, src.z, 0.0);
+        }
+
+        // Downsample into a chain of blurred mips, then accumulate them back up.
+        for level in 1..self.chain.len() {
+            let (larger, smaller) = self.chain.split_at_mut(level);
+            let previous = &larger[level - 1];
+            let half = &mut smaller[0];
+            for_each_pixel(half, |x, y| {
+                sample(previous, 2.0 * x as f32 + 1.0, 2.0 * y as f32 + 1.0)
+            });
+            blur(half, &mut self.scratch[level - 1]);
+        }
+        for level in (1..self.chain.len()).rev() {
+            let (larger, smaller) = self.chain.split_at_mut(level);
+            let small = &smaller[0];
+            let large = &mut larger[level - 1];
+            let (sx, sy) = (
+                small.width as f32 / large.width as f32,
+                small.height as f32 / large.height as f32,
+            );
+            let width = large.width;
+            for (i, pixel) in large.pixels.iter_mut().enumerate() {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                *pixel = *pixel + sample(small, (x as f32 + 0.5) * sx, (y as f32 + 0.5) * sy);
+            }
+        }
+
+        let bloom = &self.chain[0];
+        for ((dst, &src), &glow) in target.pixels.iter_mut().zip(&source.pixels).zip(&bloom.pixels) {
+            *dst = src + self.intensity * glow;
+        }
+    }
+
+    // Reallocates the chain only when the source size or the level count changes.
+    fn resize(&mut self, width: u32, height: u32) {
+        let mut sizes = vec![(width, height)];
+        for _ in 0..self.levels {
+            let (width, height) = *sizes.last().unwrap();
+            if width < 2 || height < 2 {
+                break;
+            }
+            sizes.push((width / 2, height / 2));
+        }
+
+        let current: Vec<(u32, u32)> = self.chain.iter().map(|mip| (mip.width, mip.height)).collect();
+        if current != sizes {
+            self.chain = sizes.iter().map(|&(width, height)| Image::new(width, height)).collect();
+            self.scratch = sizes[1..].iter().map(|&(width, height)| Image::new(width, height)).collect();
+        }
+    }
+}
+
+// Separable 5 tap gaussian, in place with a scratch image of the same size.
+fn blur(image: &mut RenderTargetRgba32F, scratch: &mut RenderTargetRgba32F) {
+    const WEIGHTS: [f32; 5] = [0.0625, 0.25, 0.375, 0.25, 0.0625];
+    for_each_pixel(scratch, |x, y| {
+        WEIGHTS.iter().enumerate().fold(Float4::zero(), |sum, (i, &w)| {
+            sum + w * fetch(image, x as i32 + i as i32 - 2, y as i32)
+        })
+    });
+    for_each_pixel(image, |x, y| {
+        WEIGHTS.iter().enumerate().fold(Float4::zero(), |sum, (i, &w)| {
+            sum + w * fetch(scratch, x as i32, y as i32 + i as i32 - 2)
+        })
+    });
+}
+
+pub struct Vignette {
+    pub intensity: f32,
+    // Distance from the center, relative to the half diagonal, where darkening starts.
+    pub radius: f32,
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.5,
+            radius: 0.6,
+            smoothness: 0.4,
+        }
+    }
+}
+
+impl Vignette {
+    pub fn apply(&self, source: &RenderTargetRgba32F, target: &mut RenderTargetRgba32F) {
+        let (cx, cy) = (source.width as f32 * 0.5, source.height as f32 * 0.5);
+        let half_diagonal = (cx * cx + cy * cy).sqrt().max(1.0);
+        for_each_pixel(target, |x, y| {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let distance = (dx * dx + dy * dy).sqrt() / half_diagonal;
+            let falloff = smoothstep(self.radius, self.radius + self.smoothness, distance);
+            let factor = 1.0 - self.intensity * falloff;
+            let mut color = factor * fetch(source, x as i32, y as i32);
+            color.w = fetch(source, x as i32, y as i32).w;
+            color
+        });
+    }
+}
+
+// A 3D lookup table indexed by red, green and blue in [0, 1]. Trilinear filtering needs at least
+// two entries per axis.
+pub struct Lut3D {
+    size: u32,
+    data: Vec<Float3>,
+}
+
+impl Lut3D {
+    pub fn identity(size: u32) -> Self {
+        assert!(size >= 2, "LUT size must be at least 2, got {size}");
+        let scale = 1.0 / (size - 1) as f32;
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push(Float3::new(r as f32 * scale, g as f32 * scale, b as f32 * scale));
+                }
+            }
+        }
+        Self { size, data }
+    }
+
+    // The common strip layout: `size` slices of size x size laid out horizontally, one per blue
+    // value. Strips map sRGB encoded colors to sRGB encoded colors, so values are kept encoded.
+    pub fn from_strip(texture: &Texture) -> Self {
+        let size = texture.height;
+        assert!(size >= 2, "LUT size must be at least 2, got {size}");
+        assert_eq!(texture.width, size * size, "LUT strip must be size^2 x size");
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let pixel = texture.pixels[(g * texture.width + b * size + r) as usize];
+                    data.push((1.0 / 255.0) * Float3::new(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32));
+                }
+            }
+        }
+        Self { size, data }
+    }
+
+    fn at(&self, r: u32, g: u32, b: u32) -> Float3 {
+        self.data[((b * self.size + g) * self.size + r) as usize]
+    }
+
+    pub fn sample(&self, color: Float3) -> Float3 {
+        let max = (self.size - 1) as f32;
+        let coords = [color.x, color.y, color.z].map(|c| c.clamp(0.0, 1.0) * max);
+        let base = coords.map(|c| (c.floor() as u32).min(self.size - 2));
+        let t = [0, 1, 2].map(|i| coords[i] - base[i] as f32);
+
+        let lerp = |a: Float3, b: Float3, t: f32| (1.0 - t) * a + t * b;
+        let [r, g, b] = base;
+        let c00 = lerp(self.at(r, g, b), self.at(r + 1, g, b), t[0]);
+        let c10 = lerp(self.at(r, g + 1, b), self.at(r + 1, g + 1, b), t[0]);
+        let c01 = lerp(self.at(r, g, b + 1), self.at(r + 1, g, b + 1), t[0]);
+        let c11 = lerp(self.at(r, g + 1, b + 1), self.at(r + 1, g + 1, b + 1), t[0]);
+        lerp(lerp(c00, c10, t[1]), lerp(c01, c11, t[1]), t[2])
+    }
+}
+
+// Grading LUTs are authored for display colors, so unlike the HDR effects above this runs on the
+// tonemapped, sRGB encoded target.
+pub struct ColorGrading {
+    pub lut: Lut3D,
+    pub strength: f32,
+}
+
+impl ColorGrading {
+    pub fn new(lut: Lut3D) -> Self {
+        Self { lut, strength: 1.0 }
+    }
+
+    pub fn apply(&self, target: &mut RenderTarget) {
+        for pixel in target.pixels.iter_mut() {
+            let color = (1.0 / 255.0) * Float3::new(pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+            let graded = self.lut.sample(color);
+            let mixed = (1.0 - self.strength) * color + self.strength * graded;
+            let encode = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            *pixel = Color::new(encode(mixed.x), encode(mixed.y), encode(mixed.z), pixel[3]);
+        }
+    }
+}
+
+pub struct ChromaticAberration {
+    // Offset of the red and blue channels at the image corners, in pixels.
+    pub strength: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { strength: 2.0 }
+    }
+}
+
+impl ChromaticAberration {
+    pub fn apply(&self, source: &RenderTargetRgba32F, target: &mut RenderTargetRgba32F) {
+        let (cx, cy) = (source.width as f32 * 0.5, source.height as f32 * 0.5);
+        for_each_pixel(target, |x, y| {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let (dx, dy) = ((px - cx) / cx.max(1.0), (py - cy) / cy.max(1.0));
+            let (ox, oy) = (dx * self.strength, dy * self.strength);
+            let center = fetch(source, x as i32, y as i32);
+            let red = sample(source, px + ox, py + oy);
+            let blue = sample(source, px - ox, py - oy);
+            Float4::new(red.x, center.y, blue.z, center.w)
+        });
+    }
+}
+
+pub struct Sharpen {
+    pub amount: f32,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Self { amount: 0.5 }
+    }
+}
+
+impl Sharpen {
+    pub fn apply(&self, source: &RenderTargetRgba32F, target: &mut RenderTargetRgba32F) {
+        for_each_pixel(target, |x, y| {
+            let (x, y) = (x as i32, y as i32);
+            let center = fetch(source, x, y);
+            let neighbours = fetch(source, x - 1, y)
+                + fetch(source, x + 1, y)
+                + fetch(source, x, y - 1)
+                + fetch(source, x, y + 1);
+            let mut color = center + self.amount * (4.0 * center - neighbours);
+            color.x = color.x.max(0.0);
+            color.y = color.y.max(0.0);
+            color.z = color.z.max(0.0);
+            color.w = center.w;
+            color
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_lut_returns_its_input() {
+        let lut = Lut3D::identity(2);
+        let color = Float3::new(0.25, 0.5, 1.0);
+        assert!((lut.sample(color) - color).length() < 1e-6);
+        assert!((lut.sample(Float3::new(2.0, -1.0, 0.5)) - Float3::new(1.0, 0.0, 0.5)).length() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic(expected = "LUT size must be at least 2")]
+    fn lut_rejects_a_single_entry() {
+        Lut3D::identity(1);
+    }
+}
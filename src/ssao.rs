@@ -0,0 +1,351 @@
+use crate::format::PixelFormat;
+use crate::image_view::{DepthBuffer, Image};
+use crate::math::{Float3, Float4, Matrix4};
+use std::f32::consts::PI;
+
+// Screen space ambient occlusion from a depth buffer, with normals taken from a G-buffer when
+// one exists or reconstructed from depth otherwise. The result is an occlusion factor per pixel,
+// 1 for fully open and 0 for fully occluded, meant to scale ambient lighting.
+pub struct Ssao {
+    // World space radius of the sampled hemisphere.
+    pub radius: f32,
+    // View depth tolerance, avoids self occlusion on flat surfaces.
+    pub bias: f32,
+    pub intensity: f32,
+    pub blur_radius: u32,
+    kernel: Vec<Float3>,
+    view_depth: Vec<f32>,
+    raw: Image<f32>,
+    occlusion: Image<f32>,
+}
+
+impl Default for Ssao {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+// Rotations of the kernel around the normal repeat every 4x4 pixels, which the blur averages out.
+const ROTATIONS: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0],
+];
+
+impl Ssao {
+    pub fn new(samples: u32) -> Self {
+        // Points in the +z hemisphere, denser close to the center.
+        let kernel = (0..samples)
+            .map(|i| {
+                let t = (i as f32 + 0.5) / samples as f32;
+                let phi = i as f32 * PI * (3.0 - 5f32.sqrt());
+                let cos_theta = 1.0 - t * 0.9;
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let scale = 0.1 + 0.9 * t * t;
+                scale * Float3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+            })
+            .collect();
+        Self {
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+            blur_radius: 2,
+            kernel,
+            view_depth: vec![],
+            raw: Image::new(0, 0),
+            occlusion: Image::new(0, 0),
+        }
+    }
+
+    pub fn output(&self) -> &Image<f32> {
+        &self.occlusion
+    }
+
+    // Occlusion at a fragment's pixel, e.g. `builtins.frag_coord` in a fragment shader.
+    pub fn sample(&self, frag_coord: Float4) -> f32 {
+        let x = (frag_coord.x as u32).min(self.occlusion.width.saturating_sub(1));
+        let y = (frag_coord.y as u32).min(self.occlusion.height.saturating_sub(1));
+        self.occlusion
+            .pixels
+            .get((y * self.occlusion.width + x) as usize)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    // With normals, pixels whose normal w is zero are background. Without them the background is
+    // told apart by depth alone, so `depth` must have been cleared to 1.
+    pub fn compute<P: PixelFormat>(
+        &mut self,
+        depth: &DepthBuffer,
+        normals: Option<&Image<P>>,
+        view_proj: Matrix4,
+    ) -> &Image<f32> {
+        let (width, height) = (depth.width, depth.height);
+        if let Some(normals) = normals {
+            assert_eq!((normals.width, normals.height), (width, height));
+        }
+        if (self.occlusion.width, self.occlusion.height) != (width, height) {
+            self.raw = Image::new(width, height);
+            self.occlusion = Image::new(width, height);
+        }
+        let Some(inverse_view_proj) = view_proj.inverse() else {
+            self.occlusion.pixels.fill(1.0);
+            return &self.occlusion;
+        };
+
+        let covered = |x: u32, y: u32| {
+            let index = (y * width + x) as usize;
+            match normals {
+                Some(normals) => normals.pixels[index].to_linear().w != 0.0,
+                None => depth.pixels[index] < 1.0,
+            }
+        };
+        let world_position = |x: u32, y: u32| {
+            let ndc = Float4::new(
+                (x as f32 + 0.5) / width as f32 * 2.0 - 1.0,
+                1.0 - (y as f32 + 0.5) / height as f32 * 2.0,
+                depth.pixels[(y * width + x) as usize],
+                1.0,
+            );
+            let world = inverse_view_proj * ndc;
+            (1.0 / world.w) * Float3::new(world.x, world.y, world.z)
+        };
+
+        // Clip w is the distance along the view axis, occlusion is decided by comparing it.
+        self.view_depth.clear();
+        for y in 0..height {
+            for x in 0..width {
+                let depth = if covered(x, y) {
+                    (view_proj * world_position(x, y).as_point()).w
+                } else {
+                    f32::INFINITY
+                };
+                self.view_depth.push(depth);
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = (y * width + x) as usize;
+                if !covered(x, y) {
+                    self.raw.pixels[index] = 1.0;
+                    continue;
+                }
+                let position = world_position(x, y);
+                let normal = match normals {
+                    Some(normals) => {
+                        let n = normals.pixels[index].to_linear();
+                        Float3::new(n.x, n.y, n.z).normalize()
+                    }
+                    None => reconstruct_normal(
+                        (x, y),
+                        (width, height),
+                        &self.view_depth,
+                        world_position,
+                    ),
+                };
+                // Isolated pixels have no neighbours to reconstruct a normal from.
+                if normal.length_squared() == 0.0 {
+                    self.raw.pixels[index] = 1.0;
+                    continue;
+                }
+
+                // Tangent frame around the normal, rotated per pixel.
+                let angle = 2.0 * PI * ROTATIONS[(y % 4) as usize][(x % 4) as usize] / 16.0;
+                let helper = if normal.x.abs() < 0.9 {
+                    Float3::new(1.0, 0.0, 0.0)
+                } else {
+                    Float3::new(0.0, 1.0, 0.0)
+                };
+                let tangent = helper.cross(normal).normalize();
+                let bitangent = normal.cross(tangent);
+                let (sin, cos) = angle.sin_cos();
+                let tangent_rotated = cos * tangent + sin * bitangent;
+                let bitangent_rotated = normal.cross(tangent_rotated);
+
+                let mut occlusion = 0.0;
+                for k in &self.kernel {
+                    let offset = k.x * tangent_rotated + k.y * bitangent_rotated + k.z * normal;
+                    let sample = position + self.radius * offset;
+                    let clip = view_proj * sample.as_point();
+                    if clip.w <= 0.0 {
+                        continue;
+                    }
+                    let sx = ((clip.x / clip.w * 0.5 + 0.5) * width as f32).floor();
+                    let sy = ((0.5 - clip.y / clip.w * 0.5) * height as f32).floor();
+                    if sx < 0.0 || sy < 0.0 || sx >= width as f32 || sy >= height as f32 {
+                        continue;
+                    }
+                    let scene_depth = self.view_depth[(sy as u32 * width + sx as u32) as usize];
+                    if scene_depth < clip.w - self.bias {
+                        // Occluders far outside the radius belong to other objects, fade them out.
+                        let distance = (self.view_depth[index] - scene_depth).abs();
+                        occlusion += (self.radius / distance.max(1e-4)).min(1.0);
+                    }
+                }
+                let occlusion = occlusion / self.kernel.len().max(1) as f32;
+                self.raw.pixels[index] = (1.0 - self.intensity * occlusion).clamp(0.0, 1.0);
+            }
+        }
+
+        self.blur(width, height);
+        &self.occlusion
+    }
+
+    // Box blur that skips neighbours at very different depths so occlusion doesn't bleed across
+    // silhouettes.
+    fn blur(&mut self, width: u32, height: u32) {
+        let radius = self.blur_radius as i32;
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let index = (y as u32 * width + x as u32) as usize;
+                let center_depth = self.view_depth[index];
+                if center_depth.is_infinite() {
+                    self.occlusion.pixels[index] = 1.0;
+                    continue;
+                }
+                let (mut sum, mut weight) = (0.0, 0.0);
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (nx, ny) = (x + dx, y + dy);
+                        if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                            continue;
+                        }
+                        let neighbour = (ny as u32 * width + nx as u32) as usize;
+                        if (self.view_depth[neighbour] - center_depth).abs() > 0.1 * center_depth {
+                            continue;
+                        }
+                        sum += self.raw.pixels[neighbour];
+                        weight += 1.0;
+                    }
+                }
+                self.occlusion.pixels[index] = sum / weight;
+            }
+        }
+    }
+}
+
+// Cross product of the position differences towards the neighbours closest in depth on each
+// axis, so the normal doesn't smear across depth discontinuities.
+fn reconstruct_normal(
+    (x, y): (u32, u32),
+    (width, height): (u32, u32),
+    view_depth: &[f32],
+    world_position: impl Fn(u32, u32) -> Float3,
+) -> Float3 {
+    let center = world_position(x, y);
+    let depth = view_depth[(y * width + x) as usize];
+    // Difference towards the better of the forward and backward neighbour, as a forward step.
+    let step = |forward: Option<(u32, u32)>, backward: Option<(u32, u32)>| {
+        let delta = |p: Option<(u32, u32)>| {
+            p.map(|(px, py)| ((view_depth[(py * width + px) as usize] - depth).abs(), px, py))
+                .filter(|(delta, _, _)| delta.is_finite())
+        };
+        match (delta(forward), delta(backward)) {
+            (Some(f), Some(b)) if b.0 < f.0 => center - world_position(b.1, b.2),
+            (Some(f), _) => world_position(f.1, f.2) - center,
+            (None, Some(b)) => center - world_position(b.1, b.2),
+            (None, None) => Float3::zero(),
+        }
+    };
+    let dx = step(
+        (x + 1 < width).then_some((x + 1, y)),
+        x.checked_sub(1).map(|px| (px, y)),
+    );
+    let dy = step(
+        (y + 1 < height).then_some((x, y + 1)),
+        y.checked_sub(1).map(|py| (x, py)),
+    );
+    // Screen down crossed with screen right faces the camera on any visible surface, including
+    // ones seen edge on where the view depth can't tell the two sides apart.
+    let normal = dy.cross(dx);
+    if normal.length_squared() == 0.0 {
+        return normal;
+    }
+    normal.normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 32;
+
+    // Camera at the origin looking down -z with a 90 degree field of view, so the view direction
+    // through a pixel is its NDC position at z = -1.
+    fn view_proj() -> Matrix4 {
+        Matrix4::perspective(0.1, 20.0, PI / 2.0, 1.0)
+    }
+
+    // Depth of the closest hit along each pixel's view ray, 1 where `hit` returns None.
+    fn trace(hit: impl Fn(Float3) -> Option<f32>) -> DepthBuffer {
+        let mut depth = DepthBuffer::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let direction = Float3::new(
+                    (x as f32 + 0.5) / SIZE as f32 * 2.0 - 1.0,
+                    1.0 - (y as f32 + 0.5) / SIZE as f32 * 2.0,
+                    -1.0,
+                );
+                let value = hit(direction).map_or(1.0, |t| {
+                    let clip = view_proj() * (t * direction).as_point();
+                    clip.z / clip.w
+                });
+                depth.pixels[(y * SIZE + x) as usize] = value;
+            }
+        }
+        depth
+    }
+
+    fn compute(depth: &DepthBuffer) -> Vec<f32> {
+        let mut ssao = Ssao::default();
+        ssao.compute(depth, None::<&Image<Float4>>, view_proj()).pixels.clone()
+    }
+
+    #[test]
+    fn flat_wall_is_unoccluded() {
+        let occlusion = compute(&trace(|_| Some(5.0)));
+        for value in occlusion {
+            assert!(value > 0.99, "{value}");
+        }
+    }
+
+    #[test]
+    fn concave_corner_is_occluded() {
+        // A wall at z = -5 standing on a floor at y = -1, they meet on the row where y = -0.2.
+        let occlusion = compute(&trace(|direction| {
+            let floor = if direction.y < 0.0 { -1.0 / direction.y } else { f32::INFINITY };
+            Some(floor.min(5.0))
+        }));
+        let row_average = |y: u32| {
+            let row = &occlusion[(y * SIZE) as usize..((y + 1) * SIZE) as usize];
+            row.iter().sum::<f32>() / SIZE as f32
+        };
+        let corner = 0.5 * (row_average(19) + row_average(20));
+        assert!(corner < 0.95, "{corner}");
+        // Away from the corner both planes are open.
+        for y in [4, 28] {
+            assert!(row_average(y) > 0.99, "row {y}: {}", row_average(y));
+        }
+    }
+
+    #[test]
+    fn blur_does_not_cross_depth_edges() {
+        // The left half is fully occluded and close, the right half open and far away.
+        let near = |x: u32| x < SIZE / 2;
+        let mut ssao = Ssao {
+            view_depth: (0..SIZE).map(|x| if near(x) { 1.0 } else { 5.0 }).collect(),
+            raw: Image::new(SIZE, 1),
+            occlusion: Image::new(SIZE, 1),
+            ..Ssao::default()
+        };
+        for x in 0..SIZE {
+            ssao.raw.pixels[x as usize] = if near(x) { 0.0 } else { 1.0 };
+        }
+        ssao.blur(SIZE, 1);
+        for x in 0..SIZE {
+            assert_eq!(ssao.occlusion.pixels[x as usize], ssao.raw.pixels[x as usize], "{x}");
+        }
+    }
+}
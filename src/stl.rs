@@ -0,0 +1,145 @@
+use crate::math::{Float2, Float3};
+use crate::meshes::Mesh;
+use std::path::Path;
+
+impl Mesh {
+    // STL stores unshared triangles, so every face gets its own vertices and a flat normal.
+    pub fn from_stl(path: &Path) -> Mesh {
+        Self::parse_stl(&std::fs::read(path).unwrap())
+    }
+
+    fn parse_stl(data: &[u8]) -> Mesh {
+        let triangles = if is_binary_stl(data) {
+            read_binary_triangles(data)
+        } else {
+            read_ascii_triangles(&String::from_utf8_lossy(data))
+        };
+
+        let mut mesh = Mesh::default();
+        for (stored_normal, vertices) in triangles {
+            let computed = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+            let normal = if computed.length_squared() > 0.0 {
+                computed.normalize()
+            } else if stored_normal.length_squared() > 0.0 {
+                stored_normal.normalize()
+            } else {
+                Float3::new(0.0, 0.0, 1.0)
+            };
+
+            for vertex in vertices {
+                mesh.indices.push(mesh.positions.len() as u32);
+                mesh.positions.push(vertex);
+                mesh.normals.push(normal);
+                mesh.uvs.push(Float2::zero());
+            }
+        }
+        mesh.generate_tangents();
+
+        mesh
+    }
+}
+
+// ASCII files may also start with "solid", so trust the size implied by the triangle count.
+fn is_binary_stl(data: &[u8]) -> bool {
+    if data.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    data.len() == 84 + count * 50 || !data.trim_ascii_start().starts_with(b"solid")
+}
+
+fn read_binary_triangles(data: &[u8]) -> Vec<(Float3, [Float3; 3])> {
+    data[84..]
+        .chunks_exact(50)
+        .map(|facet| {
+            let float = |i: usize| {
+                let b = &facet[4 * i..4 * i + 4];
+                f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+            };
+            let float3 = |i: usize| Float3::new(float(i), float(i + 1), float(i + 2));
+            (float3(0), [float3(3), float3(6), float3(9)])
+        })
+        .collect()
+}
+
+fn read_ascii_triangles(source: &str) -> Vec<(Float3, [Float3; 3])> {
+    let mut triangles = vec![];
+    let mut normal = Float3::zero();
+    let mut vertices: Vec<Float3> = vec![];
+
+    for line in source.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let parse = |values: &[&str]| {
+            let v: Vec<f32> = values.iter().map(|v| v.parse().unwrap_or(0.0)).collect();
+            Float3::new(v[0], v[1], v[2])
+        };
+        match tokens.as_slice() {
+            ["facet", "normal", values @ ..] if values.len() >= 3 => {
+                normal = parse(values);
+                vertices.clear();
+            }
+            ["vertex", values @ ..] if values.len() >= 3 => vertices.push(parse(values)),
+            ["endfacet", ..] => {
+                // Polygonal facets are fanned into triangles.
+                for i in 1..vertices.len().saturating_sub(1) {
+                    triangles.push((normal, [vertices[0], vertices[i], vertices[i + 1]]));
+                }
+                vertices.clear();
+            }
+            _ => {}
+        }
+    }
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_triangle(mesh: &Mesh) {
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        let x = mesh.positions.iter().map(|p| p.x).collect::<Vec<_>>();
+        assert_eq!(x, vec![0.0, 1.0, 0.0]);
+        // The flat normal comes from the winding, not from the stored facet normal.
+        for normal in &mesh.normals {
+            assert_eq!([normal.x, normal.y, normal.z], [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let source = "solid test\n\
+            facet normal 0 0 -1\n\
+            outer loop\n\
+            vertex 0 0 0\n\
+            vertex 1 0 0\n\
+            vertex 0 1 0\n\
+            endloop\n\
+            endfacet\n\
+            endsolid test\n";
+        assert_triangle(&Mesh::parse_stl(source.as_bytes()));
+    }
+
+    #[test]
+    fn reads_ascii_polygons_as_fans() {
+        let source = "solid quad\nfacet normal 0 0 1\nouter loop\n\
+            vertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nvertex 0 1 0\n\
+            endloop\nendfacet\nendsolid quad\n";
+        assert_eq!(Mesh::parse_stl(source.as_bytes()).indices.len(), 6);
+    }
+
+    #[test]
+    fn reads_binary_even_with_a_solid_header() {
+        // Binary exporters commonly start the 80 byte header with "solid" too.
+        let mut data = b"solid exported".to_vec();
+        data.resize(80, b' ');
+        data.extend(1u32.to_le_bytes());
+        let values = [0.0f32, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        for value in values {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([0, 0]);
+        assert_triangle(&Mesh::parse_stl(&data));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::format::PixelFormat;
+use crate::image_view::{Image, RenderTarget};
+use crate::math::{self, Float3, Float4};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TonemapOperator {
+    // Clamps, only exposure and the sRGB encode are applied.
+    None,
+    Reinhard,
+    Aces,
+}
+
+impl TonemapOperator {
+    pub fn next(self) -> Self {
+        match self {
+            TonemapOperator::None => TonemapOperator::Reinhard,
+            TonemapOperator::Reinhard => TonemapOperator::Aces,
+            TonemapOperator::Aces => TonemapOperator::None,
+        }
+    }
+}
+
+// Resolves a linear HDR image into the sRGB RGBA8 target handed to `Window::present`.
+#[derive(Clone, Copy, Debug)]
+pub struct Tonemapper {
+    pub operator: TonemapOperator,
+    pub exposure: f32,
+}
+
+impl Default for Tonemapper {
+    fn default() -> Self {
+        Self {
+            operator: TonemapOperator::Aces,
+            exposure: 1.0,
+        }
+    }
+}
+
+impl Tonemapper {
+    pub fn new(operator: TonemapOperator) -> Self {
+        Self {
+            operator,
+            ..Default::default()
+        }
+    }
+
+    // Exposure in stops, 0 leaves the image unchanged.
+    pub fn set_exposure_ev(&mut self, ev: f32) {
+        self.exposure = ev.exp2();
+    }
+
+    pub fn map(&self, color: Float3) -> Float3 {
+        let color = self.exposure * color;
+        match self.operator {
+            TonemapOperator::None => color,
+            TonemapOperator::Reinhard => Float3::new(
+                color.x / (1.0 + color.x),
+                color.y / (1.0 + color.y),
+                color.z / (1.0 + color.z),
+            ),
+            TonemapOperator::Aces => Float3::new(aces(color.x), aces(color.y), aces(color.z)),
+        }
+    }
+
+    pub fn apply<P: PixelFormat>(&self, hdr: &Image<P>, target: &mut RenderTarget) {
+        assert_eq!((hdr.width, hdr.height), (target.width, target.height));
+        for (dst, src) in target.pixels.iter_mut().zip(&hdr.pixels) {
+            let linear = src.to_linear();
+            let mapped = self.map(Float3::new(linear.x, linear.y, linear.z));
+            *dst = math::linear_to_srgb_color(Float4::new(mapped.x, mapped.y, mapped.z, linear.w));
+        }
+    }
+}
+
+// Narkowicz's fit of the ACES filmic curve.
+fn aces(x: f32) -> f32 {
+    let x = x.max(0.0);
+    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+}
@@ -1,5 +1,8 @@
 use crate::image_view::{RenderTarget};
+use sdl3::keyboard::Scancode;
+use sdl3::mouse::MouseButton;
 use sdl3::pixels::PixelFormat;
+use std::collections::HashSet;
 use sdl3::render::{BlendMode, Canvas, TextureCreator};
 use sdl3::video::WindowContext;
 
@@ -19,6 +22,12 @@ pub struct Window {
     texture: Texture,
     window_size: (i32, i32),
     mouse_pos: (i32, i32),
+    mouse_delta: (f32, f32),
+    wheel_delta: f32,
+    pressed_keys: HashSet<Scancode>,
+    // Keys that went down since the last poll, ignoring key repeat.
+    new_keys: HashSet<Scancode>,
+    pressed_buttons: HashSet<MouseButton>,
     running: bool,
     resized: bool,
 }
@@ -57,6 +66,11 @@ impl Window {
             texture,
             window_size: (width as i32, height as i32),
             mouse_pos: (0, 0),
+            mouse_delta: (0.0, 0.0),
+            wheel_delta: 0.0,
+            pressed_keys: HashSet::new(),
+            new_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
             running: true,
             resized: false,
         }
@@ -70,6 +84,26 @@ impl Window {
         self.mouse_pos
     }
 
+    pub fn get_mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    pub fn get_wheel_delta(&self) -> f32 {
+        self.wheel_delta
+    }
+
+    pub fn is_key_down(&self, key: Scancode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    pub fn is_key_pressed(&self, key: Scancode) -> bool {
+        self.new_keys.contains(&key)
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
     pub fn get_window_size(&self) -> (i32, i32) {
         self.window_size
     }
@@ -98,6 +132,10 @@ impl Window {
     }
 
     pub fn poll(&mut self) {
+        self.mouse_delta = (0.0, 0.0);
+        self.wheel_delta = 0.0;
+        self.new_keys.clear();
+
         for event in self.event_pump.poll_iter() {
             use sdl3::event::Event;
             match event {
@@ -123,10 +161,44 @@ impl Window {
 
                     self.resized = true;
                 }
-                Event::KeyDown { .. } => {}
-                Event::KeyUp { .. } => {}
-                Event::MouseMotion { x, y, .. } => {
+                Event::Window {
+                    win_event: sdl3::event::WindowEvent::FocusLost,
+                    ..
+                } => {
+                    self.pressed_keys.clear();
+                    self.pressed_buttons.clear();
+                }
+                Event::KeyDown {
+                    scancode: Some(scancode),
+                    repeat,
+                    ..
+                } => {
+                    self.pressed_keys.insert(scancode);
+                    if !repeat {
+                        self.new_keys.insert(scancode);
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(scancode),
+                    ..
+                } => {
+                    self.pressed_keys.remove(&scancode);
+                }
+                Event::MouseMotion {
+                    x, y, xrel, yrel, ..
+                } => {
                     self.mouse_pos = (x as i32, y as i32);
+                    self.mouse_delta.0 += xrel;
+                    self.mouse_delta.1 += yrel;
+                }
+                Event::MouseButtonDown { mouse_btn, .. } => {
+                    self.pressed_buttons.insert(mouse_btn);
+                }
+                Event::MouseButtonUp { mouse_btn, .. } => {
+                    self.pressed_buttons.remove(&mouse_btn);
+                }
+                Event::MouseWheel { y, .. } => {
+                    self.wheel_delta += y;
                 }
                 _ => {}
             }